@@ -0,0 +1,61 @@
+use crate::{button::Button, popup::Popup};
+
+use ratatui::{
+    prelude::*,
+    widgets::{Paragraph, Wrap},
+};
+
+/// A yes/no dialog. Input is left to the caller, which toggles the choice and
+/// reads it back with [`Confirm::confirmed`].
+#[derive(Debug)]
+pub struct Confirm<'a> {
+    title: &'a str,
+    message: String,
+    confirmed: bool,
+}
+
+impl<'a> Confirm<'a> {
+    pub fn new(title: &'a str, message: impl Into<String>) -> Self {
+        Self {
+            title,
+            message: message.into(),
+            confirmed: false,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.confirmed = !self.confirmed;
+    }
+
+    pub fn confirmed(&self) -> bool {
+        self.confirmed
+    }
+}
+
+impl<'a> Widget for &Confirm<'a> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let popup = Popup::new(self.title).size(50, 30);
+        popup.render_body(area, buf, |area, buf| {
+            let layout = Layout::vertical([Constraint::Fill(1), Constraint::Length(3)])
+                .margin(1)
+                .split(area);
+            Paragraph::new(self.message.as_str())
+                .wrap(Wrap { trim: true })
+                .render(layout[0], buf);
+
+            let buttons = Layout::horizontal([Constraint::Length(7), Constraint::Length(6)])
+                .flex(layout::Flex::Center)
+                .spacing(2)
+                .split(layout[1]);
+            let mut yes = Button::new("(Y)es");
+            let mut no = Button::new("(N)o");
+            if self.confirmed {
+                yes.highlight();
+            } else {
+                no.highlight();
+            }
+            yes.render(buttons[0], buf);
+            no.render(buttons[1], buf);
+        });
+    }
+}
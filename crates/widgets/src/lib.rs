@@ -1,4 +1,5 @@
 pub mod button;
+pub mod confirm;
 pub mod picker;
 pub mod popup;
 pub mod radio;
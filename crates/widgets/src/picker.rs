@@ -1,14 +1,16 @@
+use std::borrow::Cow;
+
 use crate::popup::Popup;
 
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, List},
+    widgets::{List, ListState},
 };
 
 #[derive(Debug)]
 pub struct Picker<'a, 'b, I> {
     title: &'a str,
-    items: Vec<(&'b str, I)>,
+    items: Vec<(Cow<'b, str>, I)>,
     selected: Option<Vec<usize>>,
     index: usize,
     select: PickerSelect,
@@ -21,29 +23,51 @@ pub enum PickerSelect {
 }
 
 impl<'a, 'b, I> Picker<'a, 'b, I> {
-    pub fn new(title: &'a str, items: Vec<(&'b str, I)>, select: PickerSelect) -> Self {
+    pub fn new<S>(title: &'a str, items: Vec<(S, I)>, select: PickerSelect) -> Self
+    where
+        S: Into<Cow<'b, str>>,
+    {
         Self {
             title,
-            items,
+            items: items
+                .into_iter()
+                .map(|(label, item)| (label.into(), item))
+                .collect(),
             index: 0,
             selected: None,
             select,
         }
     }
 
-    pub fn single(title: &'a str, items: Vec<(&'b str, I)>) -> Self {
+    pub fn single<S>(title: &'a str, items: Vec<(S, I)>) -> Self
+    where
+        S: Into<Cow<'b, str>>,
+    {
         Self::new(title, items, PickerSelect::Single)
     }
 
-    pub fn multiple(title: &'a str, items: Vec<(&'b str, I)>) -> Self {
+    pub fn multiple<S>(title: &'a str, items: Vec<(S, I)>) -> Self
+    where
+        S: Into<Cow<'b, str>>,
+    {
         Self::new(title, items, PickerSelect::Multiple)
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
     pub fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         self.index = (self.index + 1) % self.items.len();
     }
 
     pub fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         if self.index == 0 {
             self.index = self.items.len() - 1;
         } else {
@@ -52,6 +76,9 @@ impl<'a, 'b, I> Picker<'a, 'b, I> {
     }
 
     pub fn select(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
         if let Some(selected) = &mut self.selected {
             if selected.contains(&self.index) {
                 selected.retain(|&x| x != self.index);
@@ -79,20 +106,38 @@ impl<'a, 'b, I> Picker<'a, 'b, I> {
                 .collect()
         })
     }
+
+    fn is_selected(&self, index: usize) -> bool {
+        self.selected
+            .as_ref()
+            .is_some_and(|selected| selected.contains(&index))
+    }
 }
 
 impl<'a, 'b, I> Widget for &Picker<'a, 'b, I> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let items = self.items.iter().map(|(item, _)| item).cloned();
+        let items = self.items.iter().enumerate().map(|(i, (item, _))| {
+            let mark = match (&self.select, self.is_selected(i)) {
+                (PickerSelect::Single, true) => "◉ ",
+                (PickerSelect::Single, false) => "◎ ",
+                (PickerSelect::Multiple, true) => "[x] ",
+                (PickerSelect::Multiple, false) => "[ ] ",
+            };
+            format!("{}{}", mark, item)
+        });
         let list = List::new(items)
-            .block(Block::default().title(self.title).borders(Borders::ALL))
             .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
             .highlight_symbol(">>");
+        let mut state = ListState::default().with_selected(Some(self.index));
 
         let popup = Popup::new(self.title);
 
         popup.render_body(area, buf, |area, buf| {
-            Widget::render(&list, area, buf);
+            let area = Layout::default()
+                .margin(1)
+                .constraints([Constraint::Fill(1)])
+                .split(area)[0];
+            StatefulWidget::render(&list, area, buf, &mut state);
         });
     }
 }
@@ -3,11 +3,24 @@ use ratatui::widgets::{Block, Borders, Clear, Widget};
 
 pub struct Popup<'a> {
     title: &'a str,
+    percent_x: u16,
+    percent_y: u16,
 }
 
 impl<'a> Popup<'a> {
     pub fn new(title: &'a str) -> Self {
-        Self { title }
+        Self {
+            title,
+            percent_x: 80,
+            percent_y: 95,
+        }
+    }
+
+    /// Sets how much of the available area the popup covers, in percent
+    pub fn size(mut self, percent_x: u16, percent_y: u16) -> Self {
+        self.percent_x = percent_x;
+        self.percent_y = percent_y;
+        self
     }
 
     fn layout(&self, area: Rect) -> Rect {
@@ -23,7 +36,7 @@ impl<'a> Popup<'a> {
         B: FnOnce(Rect, &mut Buffer),
     {
         let area = self.layout(area);
-        let area = centered_rect(80, 95, area);
+        let area = centered_rect(self.percent_x, self.percent_y, area);
         self.render(area, buf);
         body(area, buf);
     }
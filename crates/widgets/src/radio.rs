@@ -19,6 +19,15 @@ impl<'a, T> RadioGroup<'a, T> {
         }
     }
 
+    pub fn select_value(&mut self, value: &T)
+    where
+        T: PartialEq,
+    {
+        if let Some(index) = self.radio_buttons.iter().position(|b| &b.value == value) {
+            self.select(index);
+        }
+    }
+
     pub fn highlight(&mut self, index: usize) {
         for (i, radio_button) in self.radio_buttons.iter_mut().enumerate() {
             if i == index {
@@ -11,11 +11,27 @@ pub enum Status {
 #[derive(Debug)]
 pub struct StatusLine {
     status: Option<Status>,
+    /// Shown on the right, for whatever the status is about
+    label: Option<String>,
+    /// Shown left of the label, for something about it that needs to stand out
+    badge: Option<String>,
 }
 
 impl StatusLine {
     pub fn new() -> Self {
-        Self { status: None }
+        Self {
+            status: None,
+            label: None,
+            badge: None,
+        }
+    }
+
+    pub fn set_badge(&mut self, badge: Option<String>) {
+        self.badge = badge;
+    }
+
+    pub fn set_label(&mut self, label: Option<String>) {
+        self.label = label;
     }
 
     pub fn set_text(&mut self, status: Status) {
@@ -29,9 +45,26 @@ impl StatusLine {
 
 impl Widget for &StatusLine {
     fn render(self, area: Rect, buf: &mut Buffer) {
+        let label = self
+            .label
+            .as_ref()
+            .map(|label| format!(" {} ", label))
+            .unwrap_or_default();
+        let badge = self
+            .badge
+            .as_ref()
+            .map(|badge| format!(" {} ", badge))
+            .unwrap_or_default();
         let layout = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints([Constraint::Min(1)].as_ref())
+            .constraints(
+                [
+                    Constraint::Min(1),
+                    Constraint::Length(badge.len() as u16),
+                    Constraint::Length(label.len() as u16),
+                ]
+                .as_ref(),
+            )
             .split(area);
         let bg = Color::Rgb(50, 50, 70);
         let text = match &self.status {
@@ -44,5 +77,11 @@ impl Widget for &StatusLine {
             None => Text::raw("").style(Style::default().bg(bg).white()),
         };
         text.render(layout[0], buf);
+        Text::raw(badge)
+            .style(Style::default().bg(Color::Red).white().bold())
+            .render(layout[1], buf);
+        Text::raw(label)
+            .style(Style::default().bg(Color::LightMagenta).black())
+            .render(layout[2], buf);
     }
 }
@@ -11,7 +11,10 @@ use tokio::sync::Mutex;
 use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle};
 
 use crate::{
-    data::{AppCommand, Ctx, Data, Store},
+    connection_test::ConnectionTest,
+    data::{AppCommand, Ctx, Data, DatabaseType, Environment, NewConnectionInfo, Store},
+    health,
+    results::{Cell, ResultSet, Summary},
     screens::main::MainScreen,
     widget::AppWidget,
 };
@@ -32,6 +35,7 @@ pub struct Runtime {
     terminal: Terminal<CrosstermBackend<Stdout>>,
     app: App,
     store: Store,
+    connect: Option<AppCommand>,
 }
 
 impl Runtime {
@@ -40,9 +44,22 @@ impl Runtime {
             terminal,
             app: App::new(),
             store: Store::new(),
+            connect: None,
         }
     }
 
+    /// Sends `command` once the saved connections are loaded, to open a
+    /// connection without going through the connection screen
+    pub fn connect(mut self, command: AppCommand) -> Self {
+        self.connect = Some(command);
+        self
+    }
+
+    pub fn query(mut self, query: &str) -> Self {
+        self.app.set_query(query);
+        self
+    }
+
     pub fn event_loop(
         app: Arc<Mutex<App>>,
         data: &Arc<RwLock<Data>>,
@@ -76,7 +93,7 @@ impl Runtime {
             app,
             mut store,
             terminal,
-            ..
+            connect,
         } = self;
 
         let (app_store_tx, app_store_rx) = tokio::sync::mpsc::unbounded_channel();
@@ -86,6 +103,11 @@ impl Runtime {
         crate::render::render_loop(terminal, app.clone(), store.data(), render_rx);
 
         Self::event_loop(app, store.data(), &app_store_tx);
+        health::check_loop(&app_store_tx);
+
+        if let Some(command) = connect {
+            app_store_tx.send(command).ok();
+        }
 
         store.run(app_store_rx, render_tx).await?;
 
@@ -130,8 +152,129 @@ impl App {
         self.state = State::MainScreen(MainScreenTabs::Querying);
     }
 
-    pub fn set_query_result(&mut self, headers: Vec<String>, rows: Vec<Vec<String>>) {
-        self.main_screen.set_output(headers, rows);
+    pub fn show_import_candidates(&mut self, candidates: Vec<NewConnectionInfo>) {
+        self.connection_screen.show_import_candidates(candidates);
+    }
+
+    pub fn set_active_connection(
+        &mut self,
+        id: usize,
+        name: String,
+        database_type: DatabaseType,
+        read_only: bool,
+        environment: Option<Environment>,
+    ) {
+        self.status_line.set_label(Some(name.clone()));
+        self.status_line
+            .set_badge(read_only.then(|| "RO".to_string()));
+        self.main_screen
+            .set_active_connection(id, name, database_type, environment);
+    }
+
+    pub fn close_connection(&mut self, id: usize) {
+        if self.main_screen.close_connection(id) {
+            self.status_line.set_label(None);
+            self.status_line.set_badge(None);
+        }
+    }
+
+    pub fn set_connection_lost(&mut self, name: String) {
+        self.status_line
+            .set_label(Some(format!("{} (reconnecting)", name)));
+        self.set_status_message(Status::Error(format!(
+            "Lost connection to {}, reconnecting",
+            name
+        )));
+    }
+
+    pub fn set_reconnected(&mut self, name: String, can_retry: bool) {
+        let message = if can_retry {
+            format!(
+                "Reconnected to {}, press ctrl+r to retry the query that failed",
+                name
+            )
+        } else {
+            format!("Reconnected to {}", name)
+        };
+        self.status_line.set_label(Some(name));
+        self.set_status_message(Status::Success(message));
+    }
+
+    pub fn set_connection_test(&mut self, result: ConnectionTest) {
+        self.connection_screen.set_connection_test(result);
+    }
+
+    pub fn set_query(&mut self, query: &str) {
+        self.main_screen.set_query(query);
+    }
+
+    pub fn start_query(&mut self, statements: Vec<String>) {
+        let message = match statements.len() {
+            1 => "Running query, esc or ctrl+c to cancel".to_string(),
+            n => format!("Running {} statements, esc or ctrl+c to cancel", n),
+        };
+        self.main_screen.start_query(statements);
+        self.set_status_message(Status::Info(message));
+    }
+
+    pub fn finish_query(&mut self) {
+        self.main_screen.finish_query();
+    }
+
+    pub fn set_query_result(
+        &mut self,
+        statement: usize,
+        result: ResultSet,
+        more: bool,
+        summary: Summary,
+    ) {
+        self.main_screen
+            .set_output(statement, &result, more, summary);
+        self.show_query_summary();
+    }
+
+    pub fn append_query_rows(&mut self, statement: usize, rows: Vec<Vec<Cell>>, more: bool) {
+        self.main_screen.append_output(statement, &rows, more);
+        if self.main_screen.is_showing(statement) {
+            self.show_query_summary();
+        }
+    }
+
+    pub fn add_query_notices(&mut self, statement: usize, notices: Vec<String>) {
+        self.main_screen.add_notices(statement, notices);
+        if self.main_screen.is_showing(statement) {
+            self.show_query_summary();
+        }
+    }
+
+    pub fn set_statement_failed(&mut self, statement: usize, error: String) {
+        self.main_screen
+            .set_statement_failed(statement, error.clone());
+        self.set_status_message(Status::Error(format!(
+            "Statement {} failed: {}",
+            statement + 1,
+            error
+        )));
+    }
+
+    fn show_query_summary(&mut self) {
+        let Some(mut line) = self.main_screen.summary_line() else {
+            return;
+        };
+        match self.main_screen.notice_count() {
+            0 => {}
+            1 => line.push_str(", 1 notice"),
+            n => line.push_str(&format!(", {} notices", n)),
+        }
+        if let Some(label) = self.main_screen.statement_label() {
+            line = format!("{}: {}", label, line);
+        }
+        let message = if self.main_screen.has_more_rows() {
+            Status::Info(line)
+        } else {
+            Status::Success(line)
+        };
+        self.set_status_message(message);
     }
 }
 
@@ -149,12 +292,33 @@ impl EventHandler for App {
                 ..
             } = key_event
             {
+                // Cancels a running query rather than quitting with it
+                if let State::MainScreen(_) = self.state {
+                    if self.main_screen.is_query_running() {
+                        tx.send(AppCommand::CancelQuery).ok();
+                        return Ok(false);
+                    }
+                }
                 return Ok(true);
             }
             // Needed or else Windows will trigger produce 2 events for each key press
             if key_event.kind != KeyEventKind::Press {
                 return Ok(false);
             }
+            // Back to the connection list to open another connection, the
+            // open ones stay open
+            if let (
+                State::MainScreen(_),
+                KeyEvent {
+                    code: KeyCode::Char('n'),
+                    modifiers: KeyModifiers::CONTROL,
+                    ..
+                },
+            ) = (&self.state, key_event)
+            {
+                self.state = State::ConnectionScreen;
+                return Ok(false);
+            }
         }
         match self.state {
             State::ConnectionScreen => {
@@ -0,0 +1,292 @@
+//! Runs a query without the terminal UI and prints the results to stdout, for
+//! scripts and CI.
+
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use color_eyre::eyre::{eyre, Result};
+use dbterm_widgets::status_line::Status;
+use serde::{Serialize, Serializer};
+use tabled::{builder::Builder, settings::Style};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+
+use crate::{
+    data::{AppCommand, Store},
+    render::RenderEvent,
+    results::{Cell, Column},
+};
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned columns
+    #[default]
+    Table,
+    Csv,
+    Tsv,
+    /// An array with an object per row
+    Json,
+}
+
+/// Opens the connection with `connect`, runs `query` through the same
+/// [`Store`] the UI uses and prints the result of each of its statements as
+/// its rows come in. Fails if connecting or any statement does.
+pub async fn run(
+    connect: AppCommand,
+    query: String,
+    format: OutputFormat,
+    continue_on_error: bool,
+) -> Result<()> {
+    // Only a connection picked by name needs the saved ones, which may not
+    // even be readable where scripts run, like CI without a keyring
+    let saved = matches!(connect, AppCommand::ConnectByName(_));
+    let mut store = Store::new()
+        .unpaged()
+        .continue_on_error(continue_on_error)
+        .load_saved(saved);
+    let (app_tx, app_rx) = unbounded_channel();
+    let (render_tx, render_rx) = unbounded_channel();
+
+    app_tx.send(connect).ok();
+    app_tx.send(AppCommand::Query(query)).ok();
+    app_tx.send(AppCommand::Quit).ok();
+    // Printed while the query runs, so its rows aren't all held on to first
+    let (stored, printed) = tokio::join!(store.run(app_rx, render_tx), print(render_rx, format));
+    stored?;
+    printed
+}
+
+/// Writes each page of rows as it comes in. Notices, counts and failed
+/// statements go to stderr, to keep stdout to the rows.
+async fn print(mut render_rx: UnboundedReceiver<RenderEvent>, format: OutputFormat) -> Result<()> {
+    let out = &mut io::stdout().lock();
+    let mut writer: Option<ResultWriter> = None;
+    while let Some(event) = render_rx.recv().await {
+        match event {
+            RenderEvent::QueryResult {
+                result, summary, ..
+            } => {
+                // Statements run one after the other, so the last one's rows
+                // are all in
+                if let Some(writer) = writer.take() {
+                    writer.finish(out)?;
+                }
+                for notice in &summary.notices {
+                    eprintln!("{}", notice);
+                }
+                if let Some(affected) = summary.rows_affected {
+                    eprintln!("{} rows affected", affected);
+                    continue;
+                }
+                let mut started = ResultWriter::start(out, format, result.columns)?;
+                started.write_rows(out, &result.rows)?;
+                writer = Some(started);
+            }
+            RenderEvent::QueryRows { rows, .. } => {
+                if let Some(writer) = &mut writer {
+                    writer.write_rows(out, &rows)?;
+                }
+            }
+            RenderEvent::Notices { notices, .. } => {
+                for notice in notices {
+                    eprintln!("{}", notice);
+                }
+            }
+            RenderEvent::StatementFailed { statement, error } => {
+                eprintln!("Statement {} failed: {}", statement + 1, error);
+            }
+            RenderEvent::StatusMessage(Status::Error(message)) => {
+                if let Some(writer) = writer.take() {
+                    writer.finish(out)?;
+                }
+                return Err(eyre!(message));
+            }
+            _ => {}
+        }
+    }
+    if let Some(writer) = writer {
+        writer.finish(out)?;
+    }
+    Ok(())
+}
+
+/// Writes a statement's rows a page at a time. Tables are lined up a page
+/// at a time too, each with its own header, like `psql` does with
+/// `FETCH_COUNT`.
+struct ResultWriter {
+    format: OutputFormat,
+    columns: Vec<Column>,
+    rows_written: usize,
+}
+
+impl ResultWriter {
+    fn start<W: Write>(out: &mut W, format: OutputFormat, columns: Vec<Column>) -> Result<Self> {
+        let headers = columns.iter().map(|column| column.name.clone());
+        match format {
+            OutputFormat::Csv => writeln!(out, "{}", csv_record(headers))?,
+            OutputFormat::Tsv => writeln!(out, "{}", tsv_record(headers))?,
+            OutputFormat::Json => write!(out, "[")?,
+            OutputFormat::Table => {}
+        }
+        Ok(Self {
+            format,
+            columns,
+            rows_written: 0,
+        })
+    }
+
+    fn write_rows<W: Write>(&mut self, out: &mut W, rows: &[Vec<Cell>]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        match self.format {
+            OutputFormat::Table => self.write_table(out, rows)?,
+            OutputFormat::Csv => {
+                for row in rows {
+                    writeln!(out, "{}", csv_record(row.iter().map(cell_text)))?;
+                }
+            }
+            OutputFormat::Tsv => {
+                for row in rows {
+                    writeln!(out, "{}", tsv_record(row.iter().map(cell_text)))?;
+                }
+            }
+            OutputFormat::Json => {
+                for (i, row) in rows.iter().enumerate() {
+                    let separator = if self.rows_written + i == 0 { "" } else { "," };
+                    let row = serde_json::to_string_pretty(&JsonRow(&self.columns, row))?;
+                    // Indented like the elements of a pretty printed array
+                    write!(out, "{}\n  {}", separator, row.replace('\n', "\n  "))?;
+                }
+            }
+        }
+        self.rows_written += rows.len();
+        Ok(())
+    }
+
+    fn finish<W: Write>(self, out: &mut W) -> Result<()> {
+        match self.format {
+            // Without rows there's still a header to show
+            OutputFormat::Table if self.rows_written == 0 => self.write_table(out, &[])?,
+            OutputFormat::Json if self.rows_written == 0 => writeln!(out, "]")?,
+            OutputFormat::Json => writeln!(out, "\n]")?,
+            _ => {}
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    fn write_table<W: Write>(&self, out: &mut W, rows: &[Vec<Cell>]) -> Result<()> {
+        let mut builder = Builder::default();
+        builder.push_record(self.columns.iter().map(|column| column.name.clone()));
+        for row in rows {
+            builder.push_record(row.iter().map(cell_text));
+        }
+        let mut table = builder.build();
+        table.with(Style::psql());
+        writeln!(out, "{}", table)?;
+        Ok(())
+    }
+}
+
+/// NULL is left empty, like `psql` does
+fn cell_text(cell: &Cell) -> String {
+    if cell.is_null() {
+        String::new()
+    } else {
+        cell.to_string()
+    }
+}
+
+/// A row as a JSON object with the keys in column order
+struct JsonRow<'a>(&'a [Column], &'a [Cell]);
+
+impl Serialize for JsonRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.0
+                .iter()
+                .map(|column| &column.name)
+                .zip(self.1.iter().map(Cell::to_json)),
+        )
+    }
+}
+
+/// Quotes fields the way RFC 4180 asks for
+fn csv_record(fields: impl Iterator<Item = String>) -> String {
+    fields
+        .map(|field| {
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Escapes tabs and line breaks like `COPY ... TO` does
+fn tsv_record(fields: impl Iterator<Item = String>) -> String {
+    fields
+        .map(|field| {
+            field
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r")
+        })
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<Column> {
+        ["id", "name"]
+            .into_iter()
+            .map(|name| Column {
+                name: name.into(),
+                type_name: String::new(),
+            })
+            .collect()
+    }
+
+    fn row(id: i64, name: &str) -> Vec<Cell> {
+        vec![Cell::Int(id), Cell::Text(name.into())]
+    }
+
+    fn written(format: OutputFormat, pages: &[Vec<Vec<Cell>>]) -> String {
+        let mut out = vec![];
+        let mut writer = ResultWriter::start(&mut out, format, columns()).unwrap();
+        for page in pages {
+            writer.write_rows(&mut out, page).unwrap();
+        }
+        writer.finish(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn json_pages_make_one_array() {
+        let pages = [vec![row(1, "a")], vec![], vec![row(2, "b")]];
+        let rows = [row(1, "a"), row(2, "b")];
+        let columns = columns();
+        let whole = rows
+            .iter()
+            .map(|row| JsonRow(&columns, row))
+            .collect::<Vec<_>>();
+        let expected = format!("{}\n", serde_json::to_string_pretty(&whole).unwrap());
+        assert_eq!(written(OutputFormat::Json, &pages), expected);
+        assert_eq!(written(OutputFormat::Json, &[]), "[]\n");
+    }
+
+    #[test]
+    fn csv_header_once() {
+        let pages = [vec![row(1, "a, b")], vec![row(2, "c")]];
+        assert_eq!(
+            written(OutputFormat::Csv, &pages),
+            "id,name\n1,\"a, b\"\n2,c\n"
+        );
+    }
+}
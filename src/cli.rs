@@ -0,0 +1,165 @@
+use std::{
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use clap::Parser;
+use color_eyre::eyre::{eyre, Result};
+
+use crate::{
+    batch::OutputFormat,
+    data::{AdvancedSettings, AppCommand, DatabaseType, NewConnectionInfo, TlsConfig},
+    files,
+};
+
+/// Explore databases from the terminal
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Connection URL like postgres://user@host/db, the path to a SQLite file,
+    /// or CSV, JSON and Parquet files to query together
+    #[arg(conflicts_with = "connection")]
+    pub targets: Vec<String>,
+
+    /// Name of a saved connection to open
+    #[arg(short, long)]
+    pub connection: Option<String>,
+
+    /// Connection URL to open when no connection or target is given
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    pub database_url: Option<String>,
+
+    /// SQL to put in the query editor
+    #[arg(short, long, conflicts_with = "file")]
+    pub query: Option<String>,
+
+    /// File to load into the query editor
+    #[arg(short, long)]
+    pub file: Option<PathBuf>,
+
+    /// Run SQL and print the result instead of starting the UI. Pass `-` to
+    /// read the SQL from stdin.
+    #[arg(short, long, value_name = "SQL", conflicts_with_all = ["query", "file"])]
+    pub execute: Option<String>,
+
+    /// How to print results when running SQL without the UI
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+
+    /// Keep running a script's statements after one fails, instead of
+    /// stopping at the first that does
+    #[arg(long)]
+    pub continue_on_error: bool,
+}
+
+impl Cli {
+    /// The command that opens the requested connection, if any. A saved
+    /// connection wins over the positional target, which wins over
+    /// `--database-url`.
+    pub fn connect_command(&self) -> Result<Option<AppCommand>> {
+        if let Some(name) = &self.connection {
+            return Ok(Some(AppCommand::ConnectByName(name.clone())));
+        }
+        let connection = match (self.targets.as_slice(), &self.database_url) {
+            ([], Some(url)) => parse_target(url)?,
+            ([], None) => return Ok(None),
+            ([target], _) => parse_target(target)?,
+            (targets, _) => data_files(targets.iter().map(PathBuf::from).collect())?,
+        };
+        Ok(Some(AppCommand::Open(connection)))
+    }
+
+    /// The SQL to run without the UI, from `--execute`, or stdin if that's
+    /// `-`. Stdin isn't read otherwise, so the UI can be started with it
+    /// piped or closed.
+    pub fn batch_query(&self) -> Result<Option<String>> {
+        match self.execute.as_deref() {
+            Some("-") => {
+                let mut sql = String::new();
+                io::stdin()
+                    .read_to_string(&mut sql)
+                    .map_err(|e| eyre!("Could not read SQL from stdin: {}", e))?;
+                Ok(Some(sql))
+            }
+            sql => Ok(sql.map(String::from)),
+        }
+    }
+
+    /// The text to pre-fill the query editor with
+    pub fn query_text(&self) -> Result<Option<String>> {
+        match (&self.query, &self.file) {
+            (Some(query), _) => Ok(Some(query.clone())),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map(Some)
+                .map_err(|e| eyre!("Could not read {}: {}", path.display(), e)),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Anything that isn't a URL or a data file is taken to be a SQLite file
+fn parse_target(target: &str) -> Result<NewConnectionInfo> {
+    let mut connection = if target.contains("://") || target.starts_with("sqlite:") {
+        NewConnectionInfo::from_connection_string(target)?
+    } else if files::is_data_file(Path::new(target)) {
+        return data_files(vec![PathBuf::from(target)]);
+    } else {
+        NewConnectionInfo {
+            name: String::new(),
+            host: String::new(),
+            port: 0,
+            user: String::new(),
+            password: String::new(),
+            database: target.to_string(),
+            data_files: vec![],
+            database_type: DatabaseType::Sqlite,
+            options: vec![],
+            tls: TlsConfig::default(),
+            ssh: None,
+            read_only: false,
+            environment: None,
+            group: None,
+            advanced: AdvancedSettings::default(),
+        }
+    };
+    connection.name = match connection.database_type {
+        DatabaseType::Sqlite | DatabaseType::Files => connection.database.clone(),
+        _ => format!(
+            "{}@{}/{}",
+            connection.user, connection.host, connection.database
+        ),
+    };
+    Ok(connection)
+}
+
+/// An in-memory database with a table for each file
+fn data_files(paths: Vec<PathBuf>) -> Result<NewConnectionInfo> {
+    if let Some(path) = paths.iter().find(|path| !files::is_data_file(path)) {
+        return Err(eyre!(
+            "Only CSV, JSON and Parquet files can be opened together, not {}",
+            path.display()
+        ));
+    }
+    let database = paths
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    Ok(NewConnectionInfo {
+        name: database.clone(),
+        host: String::new(),
+        port: 0,
+        user: String::new(),
+        password: String::new(),
+        database,
+        data_files: paths,
+        database_type: DatabaseType::Files,
+        options: vec![],
+        tls: TlsConfig::default(),
+        ssh: None,
+        read_only: false,
+        environment: None,
+        group: None,
+        advanced: AdvancedSettings::default(),
+    })
+}
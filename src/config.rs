@@ -0,0 +1,252 @@
+//! The on-disk format of the saved connections. The file carries a version
+//! number so that older files can be migrated forward instead of failing to
+//! parse, which would make every saved connection disappear.
+
+use std::{
+    fmt,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::data::ConnectionInfo;
+
+/// Bump this, and add a migration to [`MIGRATIONS`], whenever a change to
+/// [`ConnectionInfo`] can't be read from older files as is
+pub const CURRENT_VERSION: u32 = 2;
+
+/// Brings the connections of a file from version `i` to version `i + 1`
+type Migration = fn(Value) -> Result<Value>;
+
+const MIGRATIONS: &[Migration] = &[v0_to_v1, v1_to_v2];
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    version: u32,
+    next_id: usize,
+    connections: &'a [(usize, ConnectionInfo)],
+}
+
+#[derive(Deserialize)]
+struct RawEnvelope {
+    version: u32,
+    /// Missing from files written before it was kept
+    #[serde(default)]
+    next_id: usize,
+    connections: Value,
+}
+
+/// What's read from the connections file
+#[derive(Debug)]
+pub struct Saved {
+    pub connections: Vec<(usize, ConnectionInfo)>,
+    /// The id the next saved connection gets
+    pub next_id: usize,
+    /// Whether the file had to be migrated from an older version
+    pub migrated: bool,
+}
+
+/// The file was written by a newer version of dbterm. Unlike a file that
+/// can't be read, it's fine as it is, and shouldn't be moved or overwritten.
+#[derive(Debug)]
+pub struct TooNew {
+    pub version: u32,
+}
+
+impl fmt::Display for TooNew {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Saved connections are version {}, this version of dbterm only reads up to {}",
+            self.version, CURRENT_VERSION
+        )
+    }
+}
+
+impl std::error::Error for TooNew {}
+
+/// The saved connections in `path`
+pub fn read(path: &Path) -> Result<Saved> {
+    let content = std::fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&content)?;
+    // Files from before the envelope are a bare list of connections
+    let (version, next_id, mut connections) = if value.is_array() {
+        (0, 0, value)
+    } else {
+        let envelope: RawEnvelope = serde_json::from_value(value)?;
+        (envelope.version, envelope.next_id, envelope.connections)
+    };
+    if version > CURRENT_VERSION {
+        return Err(TooNew { version }.into());
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        connections = migration(connections)?;
+    }
+    Ok(Saved {
+        connections: serde_json::from_value(connections)?,
+        next_id,
+        migrated: version < CURRENT_VERSION,
+    })
+}
+
+/// Writes the connections to a temporary file next to `path` and renames it
+/// into place, so a crash halfway through never leaves a truncated file
+pub fn write(path: &Path, connections: &[(usize, ConnectionInfo)], next_id: usize) -> Result<()> {
+    let content = serde_json::to_string_pretty(&Envelope {
+        version: CURRENT_VERSION,
+        next_id,
+        connections,
+    })?;
+    let temp_path = path.with_extension("json.tmp");
+    let mut file = std::fs::File::create(&temp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Moves a file that couldn't be read out of the way, so saving new
+/// connections doesn't overwrite what was in it. Returns where it went.
+pub fn back_up(path: &Path) -> Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let backup = path.with_extension(format!("json.{}.bak", timestamp));
+    std::fs::rename(path, &backup)?;
+    Ok(backup)
+}
+
+/// Version 1 only wrapped the list of connections in an envelope
+fn v0_to_v1(connections: Value) -> Result<Value> {
+    Ok(connections)
+}
+
+/// Version 2 keeps the paths of "Files" connections in a list of their own,
+/// rather than in `database` separated by commas
+fn v1_to_v2(mut connections: Value) -> Result<Value> {
+    let list = connections
+        .as_array_mut()
+        .ok_or_else(|| eyre!("Saved connections aren't a list"))?;
+    for entry in list {
+        let Some(connection) = entry.get_mut(1).and_then(Value::as_object_mut) else {
+            continue;
+        };
+        if connection.get("database_type") != Some(&Value::from("Files")) {
+            continue;
+        }
+        let paths = connection
+            .get("database")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+            .map(Value::from)
+            .collect::<Vec<_>>();
+        connection.insert("data_files".to_string(), Value::Array(paths));
+    }
+    Ok(connections)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn temp_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "dbterm-config-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn connection(id: usize) -> Value {
+        json!({
+            "id": id,
+            "name": "local",
+            "host": "localhost",
+            "port": 5432,
+            "user": "postgres",
+            "database": "postgres",
+            "database_type": "Postgres",
+        })
+    }
+
+    #[test]
+    fn bare_list_is_migrated() {
+        let file = json!([[0, connection(0)], [3, connection(3)]]);
+        let path = temp_file("v0", &file.to_string());
+        let saved = read(&path).unwrap();
+        assert!(saved.migrated);
+        assert_eq!(saved.next_id, 0);
+        let ids = saved
+            .connections
+            .iter()
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, [0, 3]);
+        assert_eq!(saved.connections[1].1.host, "localhost");
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn missing_next_id_defaults() {
+        let file = json!({ "version": 2, "connections": [[2, connection(2)]] });
+        let path = temp_file("no-next-id", &file.to_string());
+        let saved = read(&path).unwrap();
+        assert!(!saved.migrated);
+        assert_eq!(saved.next_id, 0);
+        assert_eq!(saved.connections.len(), 1);
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_file("round-trip", "");
+        let connections = vec![(4, serde_json::from_value(connection(4)).unwrap())];
+        write(&path, &connections, 7).unwrap();
+        let saved = read(&path).unwrap();
+        assert!(!saved.migrated);
+        assert_eq!(saved.next_id, 7);
+        assert_eq!(saved.connections.len(), 1);
+        assert_eq!(saved.connections[0].0, 4);
+        assert_eq!(saved.connections[0].1.name, "local");
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn newer_version_is_refused() {
+        let file = json!({ "version": CURRENT_VERSION + 1, "connections": [] });
+        let path = temp_file("too-new", &file.to_string());
+        let error = read(&path).unwrap_err();
+        assert!(error.is::<TooNew>());
+        assert!(path.exists());
+        std::fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn data_files_are_split_out() {
+        let mut files = connection(1);
+        files["database_type"] = json!("Files");
+        files["database"] = json!("users.csv, orders.json");
+        let file = json!({ "version": 1, "connections": [[0, connection(0)], [1, files]] });
+        let path = temp_file("v1", &file.to_string());
+        let saved = read(&path).unwrap();
+        assert!(saved.migrated);
+        assert!(saved.connections[0].1.data_files.is_empty());
+        assert_eq!(
+            saved.connections[1].1.data_files,
+            [PathBuf::from("users.csv"), PathBuf::from("orders.json")]
+        );
+        std::fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,248 @@
+//! Tries out a connection from the connection form before it is saved.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{eyre, Report};
+use sea_orm::{sqlx, DbErr, RuntimeErr};
+use tokio::net::TcpStream;
+
+use crate::data::{ConnectionInfo, DbConnection};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone)]
+pub enum ConnectionTest {
+    Succeeded {
+        /// Time to connect and ask for the server version
+        latency: Duration,
+        version: String,
+    },
+    Failed {
+        kind: ConnectionErrorKind,
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConnectionErrorKind {
+    Dns,
+    Refused,
+    Auth,
+    Tls,
+    Timeout,
+    Other,
+}
+
+impl fmt::Display for ConnectionErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            ConnectionErrorKind::Dns => "Host not found",
+            ConnectionErrorKind::Refused => "Connection refused",
+            ConnectionErrorKind::Auth => "Authentication failed",
+            ConnectionErrorKind::Tls => "TLS error",
+            ConnectionErrorKind::Timeout => "Timed out",
+            ConnectionErrorKind::Other => "Connection failed",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Connects, asks for the server version and disconnects again
+pub async fn run(connection: &ConnectionInfo) -> ConnectionTest {
+    let start = Instant::now();
+    let result = tokio::time::timeout(TIMEOUT, async {
+        // The pool keeps retrying refused connections until it times out, so
+        // reach the server once by hand first. Hosts behind an ssh tunnel
+        // can only be reached from the jump host.
+        if !connection.database_type.is_local() && connection.ssh.is_none() {
+            TcpStream::connect((connection.host.as_str(), connection.port))
+                .await
+                .map_err(|e| eyre!(e))?;
+        }
+        let db = DbConnection::connect(connection).await?;
+        db.server_version().await
+    })
+    .await;
+
+    match result {
+        Ok(Ok(version)) => ConnectionTest::Succeeded {
+            latency: start.elapsed(),
+            version,
+        },
+        Ok(Err(e)) => ConnectionTest::Failed {
+            kind: categorize(&e),
+            message: e.to_string(),
+        },
+        Err(_) => ConnectionTest::Failed {
+            kind: ConnectionErrorKind::Timeout,
+            message: format!("No answer within {} seconds", TIMEOUT.as_secs()),
+        },
+    }
+}
+
+fn categorize(error: &Report) -> ConnectionErrorKind {
+    if let Some(io) = error.downcast_ref::<std::io::Error>() {
+        if io.kind() == std::io::ErrorKind::ConnectionRefused {
+            return ConnectionErrorKind::Refused;
+        }
+    }
+    if let Some(DbErr::Conn(RuntimeErr::SqlxError(error))) = error.downcast_ref::<DbErr>() {
+        match error {
+            sqlx::Error::Tls(_) => return ConnectionErrorKind::Tls,
+            sqlx::Error::Io(io) if io.kind() == std::io::ErrorKind::ConnectionRefused => {
+                return ConnectionErrorKind::Refused
+            }
+            sqlx::Error::PoolTimedOut => return ConnectionErrorKind::Timeout,
+            // SQLSTATE class 28 is "invalid authorization specification" for
+            // both Postgres and MySQL
+            sqlx::Error::Database(e) if e.code().is_some_and(|code| code.starts_with("28")) => {
+                return ConnectionErrorKind::Auth
+            }
+            _ => {}
+        }
+    }
+
+    // Drivers and ssh don't always give more than a message to go on
+    let message = error.to_string().to_lowercase();
+    let mentions = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+    if mentions(&["lookup", "resolve", "name or service not known", "nodename"]) {
+        ConnectionErrorKind::Dns
+    } else if mentions(&["refused"]) {
+        ConnectionErrorKind::Refused
+    } else if mentions(&[
+        "password",
+        "authentication",
+        "access denied",
+        "permission denied",
+    ]) {
+        ConnectionErrorKind::Auth
+    } else if mentions(&["tls", "ssl", "certificate"]) {
+        ConnectionErrorKind::Tls
+    } else if mentions(&["timed out", "timeout"]) {
+        ConnectionErrorKind::Timeout
+    } else {
+        ConnectionErrorKind::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, error::Error, io};
+
+    use sea_orm::sqlx::error::{DatabaseError, ErrorKind};
+
+    use super::*;
+
+    /// An error the server sent back, with its SQLSTATE
+    #[derive(Debug)]
+    struct ServerError {
+        code: &'static str,
+        message: &'static str,
+    }
+
+    impl fmt::Display for ServerError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.message)
+        }
+    }
+
+    impl Error for ServerError {}
+
+    impl DatabaseError for ServerError {
+        fn message(&self) -> &str {
+            self.message
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.code))
+        }
+
+        fn as_error(&self) -> &(dyn Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    /// An error the way connecting through sea-orm reports it
+    fn sqlx_error(error: sqlx::Error) -> Report {
+        Report::new(DbErr::Conn(RuntimeErr::SqlxError(error)))
+    }
+
+    fn server_error(code: &'static str, message: &'static str) -> Report {
+        sqlx_error(sqlx::Error::Database(Box::new(ServerError {
+            code,
+            message,
+        })))
+    }
+
+    #[test]
+    fn unknown_hosts_are_dns_errors() {
+        let error =
+            io::Error::other("failed to lookup address information: Name or service not known");
+        assert_eq!(categorize(&Report::new(error)), ConnectionErrorKind::Dns);
+        let error = eyre!("SSH tunnel to bastion failed: ssh: Could not resolve hostname bastion");
+        assert_eq!(categorize(&error), ConnectionErrorKind::Dns);
+    }
+
+    #[test]
+    fn refused_connections_are_found() {
+        let refused = || io::Error::from(io::ErrorKind::ConnectionRefused);
+        assert_eq!(
+            categorize(&Report::new(refused())),
+            ConnectionErrorKind::Refused
+        );
+        assert_eq!(
+            categorize(&sqlx_error(sqlx::Error::Io(refused()))),
+            ConnectionErrorKind::Refused
+        );
+    }
+
+    #[test]
+    fn auth_errors_are_found_by_sqlstate() {
+        let postgres = server_error("28P01", "password authentication failed for user \"app\"");
+        assert_eq!(categorize(&postgres), ConnectionErrorKind::Auth);
+        let mysql = server_error("28000", "Access denied for user 'app'@'10.0.0.1'");
+        assert_eq!(categorize(&mysql), ConnectionErrorKind::Auth);
+        let ssh =
+            eyre!("SSH tunnel to bastion failed: app@bastion: Permission denied (publickey).");
+        assert_eq!(categorize(&ssh), ConnectionErrorKind::Auth);
+    }
+
+    #[test]
+    fn tls_errors_are_found() {
+        let error = sqlx::Error::Tls("invalid peer certificate: UnknownIssuer".into());
+        assert_eq!(categorize(&sqlx_error(error)), ConnectionErrorKind::Tls);
+        let error = server_error("08P01", "server does not support SSL, but SSL was required");
+        assert_eq!(categorize(&error), ConnectionErrorKind::Tls);
+    }
+
+    #[test]
+    fn timeouts_are_found() {
+        let error = sqlx_error(sqlx::Error::PoolTimedOut);
+        assert_eq!(categorize(&error), ConnectionErrorKind::Timeout);
+        let error = eyre!("SSH tunnel to bastion timed out");
+        assert_eq!(categorize(&error), ConnectionErrorKind::Timeout);
+    }
+
+    #[test]
+    fn anything_else_is_other() {
+        let error = server_error("3D000", "database \"sales\" does not exist");
+        assert_eq!(categorize(&error), ConnectionErrorKind::Other);
+        let error = sqlx_error(sqlx::Error::Protocol("unexpected message".into()));
+        assert_eq!(categorize(&error), ConnectionErrorKind::Other);
+    }
+}
@@ -0,0 +1,583 @@
+//! Loads CSV, JSON and Parquet files into an in-memory SQLite database, a
+//! table per file, so exports can be queried and joined like any database.
+
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::{eyre, Result};
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::Field,
+};
+use sea_orm::{
+    ConnectionTrait, DatabaseBackend, DatabaseConnection, Statement, TransactionTrait, Value,
+};
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+/// The most parameters SQLite takes in one statement, in versions before 3.32
+const MAX_PARAMETERS: usize = 999;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Csv,
+    Tsv,
+    Json,
+    /// One JSON document per line
+    JsonLines,
+    Parquet,
+}
+
+fn format(path: &Path) -> Option<Format> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "csv" => Some(Format::Csv),
+        "tsv" => Some(Format::Tsv),
+        "json" => Some(Format::Json),
+        "ndjson" | "jsonl" => Some(Format::JsonLines),
+        "parquet" => Some(Format::Parquet),
+        _ => None,
+    }
+}
+
+/// Whether `path` is a file that can be loaded, going by its extension
+pub fn is_data_file(path: &Path) -> bool {
+    format(path).is_some()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+}
+
+impl Cell {
+    /// Numbers are only taken as numbers if they'd read back the same, so
+    /// zip codes, ids with leading zeros and integers too big for SQLite stay
+    /// text
+    fn infer(text: &str) -> Self {
+        if text.is_empty() {
+            return Cell::Null;
+        }
+        let numeric = text
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E'));
+        if !numeric {
+            return Cell::Text(text.to_string());
+        }
+        match (text.parse::<i64>(), text.parse::<f64>()) {
+            (Ok(integer), _) if integer.to_string() == text => Cell::Integer(integer),
+            (Err(_), Ok(real)) if real.to_string() == text => Cell::Real(real),
+            _ => Cell::Text(text.to_string()),
+        }
+    }
+
+    fn from_json(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Cell::Null,
+            serde_json::Value::Bool(value) => Cell::Integer(*value as i64),
+            serde_json::Value::Number(number) => match number.as_i64() {
+                Some(integer) => Cell::Integer(integer),
+                None => number.as_f64().map(Cell::Real).unwrap_or(Cell::Null),
+            },
+            serde_json::Value::String(text) => Cell::Text(text.clone()),
+            // Nested values are kept as JSON, for SQLite's json functions
+            value => Cell::Text(value.to_string()),
+        }
+    }
+
+    fn from_parquet(field: &Field) -> Self {
+        match field {
+            Field::Null => Cell::Null,
+            Field::Bool(value) => Cell::Integer(*value as i64),
+            Field::Byte(value) => Cell::Integer(*value as i64),
+            Field::Short(value) => Cell::Integer(*value as i64),
+            Field::Int(value) => Cell::Integer(*value as i64),
+            Field::Long(value) => Cell::Integer(*value),
+            Field::UByte(value) => Cell::Integer(*value as i64),
+            Field::UShort(value) => Cell::Integer(*value as i64),
+            Field::UInt(value) => Cell::Integer(*value as i64),
+            Field::ULong(value) => match i64::try_from(*value) {
+                Ok(value) => Cell::Integer(value),
+                Err(_) => Cell::Text(value.to_string()),
+            },
+            Field::Float(value) => Cell::Real(*value as f64),
+            Field::Double(value) => Cell::Real(*value),
+            Field::Str(value) => Cell::Text(value.clone()),
+            // Dates, timestamps, decimals and nested values, as parquet prints them
+            field => Cell::Text(field.to_string()),
+        }
+    }
+
+    fn column_type(&self) -> Option<ColumnType> {
+        match self {
+            Cell::Null => None,
+            Cell::Integer(_) => Some(ColumnType::Integer),
+            Cell::Real(_) => Some(ColumnType::Real),
+            Cell::Text(_) => Some(ColumnType::Text),
+        }
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Cell::Null => Value::from(None::<String>),
+            Cell::Integer(integer) => Value::from(integer),
+            Cell::Real(real) => Value::from(real),
+            Cell::Text(text) => Value::from(text),
+        }
+    }
+}
+
+/// SQLite column types, each able to hold the ones before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ColumnType {
+    Integer,
+    Real,
+    Text,
+}
+
+impl ColumnType {
+    fn sql(&self) -> &'static str {
+        match self {
+            ColumnType::Integer => "INTEGER",
+            ColumnType::Real => "REAL",
+            ColumnType::Text => "TEXT",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Table {
+    columns: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    /// The narrowest type holding every value of each column
+    fn column_types(&self) -> Vec<ColumnType> {
+        (0..self.columns.len())
+            .map(|i| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row[i].column_type())
+                    .max()
+                    .unwrap_or(ColumnType::Text)
+            })
+            .collect()
+    }
+}
+
+/// Creates a table for each file and fills it. Tables are named after the
+/// file, without its extension.
+pub async fn load(db: &DatabaseConnection, paths: &[PathBuf]) -> Result<()> {
+    let mut tables = HashSet::new();
+    for path in paths {
+        let table = read(path).map_err(|e| eyre!("Could not load {}: {}", path.display(), e))?;
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or_default();
+        let name = unique_name(&identifier(stem), &mut tables);
+        insert(db, &name, table).await?;
+    }
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Table> {
+    let format = format(path).ok_or_else(|| eyre!("Not a CSV, JSON or Parquet file"))?;
+    let content = || std::fs::read_to_string(path);
+    match format {
+        Format::Csv => Ok(read_delimited(&content()?, ',')),
+        Format::Tsv => Ok(read_delimited(&content()?, '\t')),
+        Format::Json => read_json(&content()?),
+        Format::JsonLines => read_json_lines(&content()?),
+        Format::Parquet => read_parquet(path),
+    }
+}
+
+async fn insert(db: &DatabaseConnection, name: &str, table: Table) -> Result<()> {
+    if table.columns.is_empty() {
+        return Err(eyre!("{} has no columns", name));
+    }
+    let columns = table
+        .columns
+        .iter()
+        .zip(table.column_types())
+        .map(|(column, column_type)| format!("{} {}", quote(column), column_type.sql()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let placeholders = format!("({})", vec!["?"; table.columns.len()].join(", "));
+    let rows_per_statement = (MAX_PARAMETERS / table.columns.len()).max(1);
+
+    let transaction = db.begin().await?;
+    transaction
+        .execute_unprepared(&format!("create table {} ({})", quote(name), columns))
+        .await?;
+    let mut rows = table.rows.into_iter().peekable();
+    while rows.peek().is_some() {
+        let chunk = rows.by_ref().take(rows_per_statement).collect::<Vec<_>>();
+        let sql = format!(
+            "insert into {} values {}",
+            quote(name),
+            vec![placeholders.as_str(); chunk.len()].join(", ")
+        );
+        let values = chunk
+            .into_iter()
+            .flatten()
+            .map(Cell::into_value)
+            .collect::<Vec<_>>();
+        transaction
+            .execute(Statement::from_sql_and_values(
+                DatabaseBackend::Sqlite,
+                sql,
+                values,
+            ))
+            .await?;
+    }
+    transaction.commit().await?;
+    Ok(())
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// A table name that can be typed without quotes
+fn identifier(name: &str) -> String {
+    let mut identifier = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+/// `name`, or `name_2`, `name_3` and so on if it's taken. SQLite names
+/// ignore case, so the comparison does too.
+fn unique_name(name: &str, taken: &mut HashSet<String>) -> String {
+    let mut unique = name.to_string();
+    let mut suffix = 2;
+    while !taken.insert(unique.to_lowercase()) {
+        unique = format!("{}_{}", name, suffix);
+        suffix += 1;
+    }
+    unique
+}
+
+/// Column names from a header, with blank ones filled in and duplicates
+/// numbered. Rows wider than the header get extra columns.
+fn column_names(header: Vec<String>, width: usize) -> Vec<String> {
+    let mut taken = HashSet::new();
+    (0..width.max(header.len()))
+        .map(|i| {
+            let name = header
+                .get(i)
+                .map(|name| name.trim())
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("column_{}", i + 1));
+            unique_name(&name, &mut taken)
+        })
+        .collect()
+}
+
+/// CSV as in RFC 4180: fields with delimiters, quotes or line breaks are
+/// quoted, and quotes in them are doubled. The first record is the header.
+fn read_delimited(content: &str, delimiter: char) -> Table {
+    let mut records = parse_delimited(content, delimiter)
+        .into_iter()
+        // Blank lines
+        .filter(|record| !(record.len() == 1 && record[0].is_empty()));
+    let header = records.next().unwrap_or_default();
+    let records = records.collect::<Vec<_>>();
+    let width = records.iter().map(Vec::len).max().unwrap_or_default();
+    let columns = column_names(header, width);
+    let rows = records
+        .into_iter()
+        .map(|record| {
+            let mut row = record
+                .iter()
+                .map(|field| Cell::infer(field))
+                .collect::<Vec<_>>();
+            row.resize(columns.len(), Cell::Null);
+            row
+        })
+        .collect();
+    Table { columns, rows }
+}
+
+fn parse_delimited(content: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = content.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// A JSON object with its keys in the order of the file, which
+/// `serde_json::Map` doesn't keep
+struct Object(Vec<(String, serde_json::Value)>);
+
+impl<'de> Deserialize<'de> for Object {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ObjectVisitor;
+
+        impl<'de> Visitor<'de> for ObjectVisitor {
+            type Value = Object;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a JSON object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Object, A::Error> {
+                let mut fields = vec![];
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+                Ok(Object(fields))
+            }
+        }
+
+        deserializer.deserialize_map(ObjectVisitor)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Record {
+    Object(Object),
+    /// Anything but an object goes in a single `value` column
+    Value(serde_json::Value),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Document {
+    Records(Vec<Record>),
+    Record(Record),
+}
+
+/// An array of objects, each a row, or a single object
+fn read_json(content: &str) -> Result<Table> {
+    let records = match serde_json::from_str(content)? {
+        Document::Records(records) => records,
+        Document::Record(record) => vec![record],
+    };
+    Ok(json_table(records))
+}
+
+fn read_json_lines(content: &str) -> Result<Table> {
+    let records = content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect::<Result<Vec<Record>, _>>()?;
+    Ok(json_table(records))
+}
+
+/// Every key of every object becomes a column, in the order they're first seen
+fn json_table(records: Vec<Record>) -> Table {
+    let mut keys: Vec<String> = vec![];
+    for record in &records {
+        let record_keys = match record {
+            Record::Object(Object(fields)) => fields.iter().map(|(key, _)| key.as_str()).collect(),
+            Record::Value(_) => vec!["value"],
+        };
+        for key in record_keys {
+            if !keys.iter().any(|seen| seen == key) {
+                keys.push(key.to_string());
+            }
+        }
+    }
+    let rows = records
+        .into_iter()
+        .map(|record| {
+            let mut row = vec![Cell::Null; keys.len()];
+            let fields = match record {
+                Record::Object(Object(fields)) => fields,
+                Record::Value(value) => vec![("value".to_string(), value)],
+            };
+            for (key, value) in fields {
+                if let Some(i) = keys.iter().position(|seen| *seen == key) {
+                    row[i] = Cell::from_json(&value);
+                }
+            }
+            row
+        })
+        .collect();
+    // Keys differing only in case would be the same column to SQLite
+    let columns = column_names(keys, 0);
+    Table { columns, rows }
+}
+
+fn read_parquet(path: &Path) -> Result<Table> {
+    let reader = SerializedFileReader::new(File::open(path)?)?;
+    let header = reader
+        .metadata()
+        .file_metadata()
+        .schema_descr()
+        .root_schema()
+        .get_fields()
+        .iter()
+        .map(|field| field.name().to_string())
+        .collect::<Vec<_>>();
+    let columns = column_names(header, 0);
+    let mut rows = vec![];
+    for row in reader.get_row_iter(None)? {
+        let mut cells = row?
+            .get_column_iter()
+            .map(|(_, field)| Cell::from_parquet(field))
+            .collect::<Vec<_>>();
+        cells.resize(columns.len(), Cell::Null);
+        rows.push(cells);
+    }
+    Ok(Table { columns, rows })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Cell {
+        Cell::Text(text.to_string())
+    }
+
+    #[test]
+    fn quoted_fields_keep_delimiters_and_quotes() {
+        let table = read_delimited("name,quote\n\"Doe, Jane\",\"She said \"\"hi\"\"\"\n", ',');
+        assert_eq!(table.columns, ["name", "quote"]);
+        assert_eq!(table.rows, [[text("Doe, Jane"), text("She said \"hi\"")]]);
+    }
+
+    #[test]
+    fn quoted_fields_keep_line_breaks() {
+        let table = read_delimited("id,note\r\n1,\"first\r\nsecond\"\r\n2,third\r\n", ',');
+        assert_eq!(
+            table.rows,
+            [
+                [Cell::Integer(1), text("first\r\nsecond")],
+                [Cell::Integer(2), text("third")],
+            ]
+        );
+    }
+
+    #[test]
+    fn byte_order_marks_are_dropped() {
+        let table = read_delimited("\u{feff}id\tname\n1\tone\n", '\t');
+        assert_eq!(table.columns, ["id", "name"]);
+    }
+
+    #[test]
+    fn ragged_rows_are_filled_in() {
+        let table = read_delimited("a,a,\n1\n1,2,3,4\n\n", ',');
+        assert_eq!(table.columns, ["a", "a_2", "column_3", "column_4"]);
+        assert_eq!(
+            table.rows,
+            [
+                vec![Cell::Integer(1), Cell::Null, Cell::Null, Cell::Null],
+                vec![
+                    Cell::Integer(1),
+                    Cell::Integer(2),
+                    Cell::Integer(3),
+                    Cell::Integer(4)
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_are_inferred_if_they_read_back_the_same() {
+        assert_eq!(Cell::infer(""), Cell::Null);
+        assert_eq!(Cell::infer("42"), Cell::Integer(42));
+        assert_eq!(Cell::infer("-7"), Cell::Integer(-7));
+        assert_eq!(Cell::infer("0"), Cell::Integer(0));
+        assert_eq!(Cell::infer("1.5"), Cell::Real(1.5));
+        assert_eq!(Cell::infer("0.25"), Cell::Real(0.25));
+        for kept in [
+            "00501",
+            "+5",
+            "1.50",
+            "1e3",
+            "9223372036854775808",
+            "1-2",
+            "abc",
+        ] {
+            assert_eq!(Cell::infer(kept), text(kept), "{}", kept);
+        }
+    }
+
+    #[test]
+    fn json_arrays_become_rows() {
+        let table = read_json(r#"[{"id": 1, "tags": ["a"]}, {"name": "two", "id": 2.5}]"#).unwrap();
+        assert_eq!(table.columns, ["id", "tags", "name"]);
+        assert_eq!(
+            table.rows,
+            [
+                [Cell::Integer(1), text(r#"["a"]"#), Cell::Null],
+                [Cell::Real(2.5), Cell::Null, text("two")],
+            ]
+        );
+        let table = read_json(r#"{"ok": true}"#).unwrap();
+        assert_eq!(table.rows, [[Cell::Integer(1)]]);
+    }
+
+    #[test]
+    fn json_keys_differing_in_case_are_separate_columns() {
+        let table = read_json(r#"{"id": 1, "ID": 2}"#).unwrap();
+        assert_eq!(table.columns, ["id", "ID_2"]);
+        assert_eq!(table.rows, [[Cell::Integer(1), Cell::Integer(2)]]);
+    }
+
+    #[test]
+    fn json_lines_are_rows() {
+        let table = read_json_lines("{\"id\": 1}\n\n3\n{\"id\": null}\n").unwrap();
+        assert_eq!(table.columns, ["id", "value"]);
+        assert_eq!(
+            table.rows,
+            [
+                [Cell::Integer(1), Cell::Null],
+                [Cell::Null, Cell::Integer(3)],
+                [Cell::Null, Cell::Null],
+            ]
+        );
+        assert!(read_json_lines("{\"id\": 1}\n{\"id\":").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+//! Keeps an eye on the active connection so a dropped network or restarted
+//! server is noticed, and reconnected to, without the user's help.
+
+use std::time::{Duration, Instant};
+
+use clokwerk::{AsyncScheduler, TimeUnits};
+use sea_orm::{sqlx, DbErr, RuntimeErr};
+use tokio::{sync::mpsc::UnboundedSender, task::JoinHandle};
+
+use crate::data::AppCommand;
+
+const CHECK_INTERVAL_SECONDS: u32 = 5;
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Asks the store to check on the active connection every few seconds
+pub fn check_loop(app_tx: &UnboundedSender<AppCommand>) -> JoinHandle<()> {
+    let tx = app_tx.clone();
+    let mut scheduler = AsyncScheduler::new();
+    scheduler
+        .every(CHECK_INTERVAL_SECONDS.seconds())
+        .run(move || {
+            let tx = tx.clone();
+            async move {
+                tx.send(AppCommand::HealthCheck).ok();
+            }
+        });
+    tokio::spawn(async move {
+        loop {
+            scheduler.run_pending().await;
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    })
+}
+
+/// When to next try reconnecting a connection that was lost, doubling the
+/// wait after every failed attempt
+#[derive(Debug)]
+pub struct Backoff {
+    attempts: u32,
+    next_attempt: Instant,
+}
+
+impl Backoff {
+    /// The first attempt is due right away
+    pub fn new() -> Self {
+        Self {
+            attempts: 0,
+            next_attempt: Instant::now(),
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        Instant::now() >= self.next_attempt
+    }
+
+    pub fn failed(&mut self) {
+        self.next_attempt = Instant::now() + delay(self.attempts);
+        self.attempts += 1;
+    }
+}
+
+/// How long to wait after `attempts` earlier failures and the one that just
+/// happened
+fn delay(attempts: u32) -> Duration {
+    Duration::from_secs(1 << attempts.min(6)).min(MAX_BACKOFF)
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a query failed because the connection is gone, rather than
+/// because of the query itself
+pub fn is_connection_error(error: &DbErr) -> bool {
+    match error {
+        DbErr::ConnectionAcquire(_) | DbErr::Conn(_) => true,
+        DbErr::Query(RuntimeErr::SqlxError(error)) | DbErr::Exec(RuntimeErr::SqlxError(error)) => {
+            matches!(
+                error,
+                sqlx::Error::Io(_)
+                    | sqlx::Error::PoolTimedOut
+                    | sqlx::Error::PoolClosed
+                    | sqlx::Error::WorkerCrashed
+            )
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use sea_orm::ConnAcquireErr;
+
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_a_minute() {
+        let delays = (0..8).map(|attempts| delay(attempts).as_secs());
+        assert_eq!(delays.collect::<Vec<_>>(), [1, 2, 4, 8, 16, 32, 60, 60]);
+        assert_eq!(delay(u32::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn backoff_waits_after_failing() {
+        let mut backoff = Backoff::new();
+        assert!(backoff.is_due());
+        backoff.failed();
+        assert!(!backoff.is_due());
+        assert_eq!(backoff.attempts, 1);
+    }
+
+    fn sqlx_error(error: sqlx::Error) -> DbErr {
+        DbErr::Query(RuntimeErr::SqlxError(error))
+    }
+
+    #[test]
+    fn lost_connections_are_connection_errors() {
+        let reset = io::Error::new(io::ErrorKind::ConnectionReset, "reset by peer");
+        assert!(is_connection_error(&sqlx_error(sqlx::Error::Io(reset))));
+        assert!(is_connection_error(&sqlx_error(sqlx::Error::PoolTimedOut)));
+        assert!(is_connection_error(&DbErr::Exec(RuntimeErr::SqlxError(
+            sqlx::Error::PoolClosed
+        ))));
+        assert!(is_connection_error(&DbErr::ConnectionAcquire(
+            ConnAcquireErr::Timeout
+        )));
+        assert!(is_connection_error(&DbErr::Conn(RuntimeErr::Internal(
+            "gone".to_string()
+        ))));
+    }
+
+    #[test]
+    fn failed_queries_are_not_connection_errors() {
+        assert!(!is_connection_error(&sqlx_error(sqlx::Error::RowNotFound)));
+        assert!(!is_connection_error(&sqlx_error(sqlx::Error::Protocol(
+            "unexpected message".to_string()
+        ))));
+        assert!(!is_connection_error(&DbErr::Custom("bad".to_string())));
+        assert!(!is_connection_error(&DbErr::Query(RuntimeErr::Internal(
+            "syntax error".to_string()
+        ))));
+    }
+}
@@ -0,0 +1,307 @@
+//! Finds connections already configured for psql and the mysql client, so they
+//! can be imported instead of typed in again.
+
+use std::path::PathBuf;
+
+use crate::data::{AdvancedSettings, DatabaseType, NewConnectionInfo, TlsConfig};
+
+/// Every connection found in `~/.pgpass`, `~/.pg_service.conf` and `~/.my.cnf`.
+/// Missing or unreadable files are skipped.
+pub fn scan() -> Vec<NewConnectionInfo> {
+    let pgpass = read(pgpass_path())
+        .map(|content| parse_pgpass(&content))
+        .unwrap_or_default();
+    let services = read(pg_service_path())
+        .map(|content| parse_pg_service(&content, &pgpass))
+        .unwrap_or_default();
+    let my_cnf = read(home_file(".my.cnf"))
+        .map(|content| parse_my_cnf(&content))
+        .unwrap_or_default();
+
+    let mut found: Vec<NewConnectionInfo> = vec![];
+    for connection in services
+        .into_iter()
+        .chain(pgpass.into_iter().filter_map(PgpassEntry::into_connection))
+        .chain(my_cnf)
+    {
+        if !found.iter().any(|c| c.target() == connection.target()) {
+            found.push(connection);
+        }
+    }
+    found
+}
+
+fn read(path: Option<PathBuf>) -> Option<String> {
+    std::fs::read_to_string(path?).ok()
+}
+
+fn home_file(name: &str) -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(name))
+}
+
+fn pgpass_path() -> Option<PathBuf> {
+    std::env::var_os("PGPASSFILE")
+        .map(PathBuf::from)
+        .or_else(|| home_file(".pgpass"))
+}
+
+fn pg_service_path() -> Option<PathBuf> {
+    std::env::var_os("PGSERVICEFILE")
+        .map(PathBuf::from)
+        .or_else(|| home_file(".pg_service.conf"))
+}
+
+fn postgres(name: String) -> NewConnectionInfo {
+    NewConnectionInfo {
+        name,
+        host: "localhost".into(),
+        port: 5432,
+        user: String::new(),
+        password: String::new(),
+        database: String::new(),
+        data_files: vec![],
+        database_type: DatabaseType::Postgres,
+        options: vec![],
+        tls: TlsConfig::default(),
+        ssh: None,
+        read_only: false,
+        environment: None,
+        group: None,
+        advanced: AdvancedSettings::default(),
+    }
+}
+
+/// A `hostname:port:database:username:password` line, where any of the first
+/// four fields may be `*`
+struct PgpassEntry {
+    host: String,
+    port: String,
+    database: String,
+    user: String,
+    password: String,
+}
+
+impl PgpassEntry {
+    fn matches(&self, connection: &NewConnectionInfo) -> bool {
+        let field = |pattern: &str, value: &str| pattern == "*" || pattern == value;
+        field(&self.host, &connection.host)
+            && field(&self.port, &connection.port.to_string())
+            && field(&self.database, &connection.database)
+            && field(&self.user, &connection.user)
+    }
+
+    /// Wildcard hosts and users don't describe a single connection
+    fn into_connection(self) -> Option<NewConnectionInfo> {
+        if self.host == "*" || self.user == "*" {
+            return None;
+        }
+        let database = match self.database.as_str() {
+            "*" => "postgres".to_string(),
+            _ => self.database,
+        };
+        let mut connection = postgres(format!("{}@{}/{}", self.user, self.host, database));
+        connection.port = self.port.parse().unwrap_or(5432);
+        connection.host = self.host;
+        connection.user = self.user;
+        connection.password = self.password;
+        connection.database = database;
+        Some(connection)
+    }
+}
+
+fn parse_pgpass(content: &str) -> Vec<PgpassEntry> {
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            // `\:` and `\\` escape colons and backslashes
+            let mut fields = vec![String::new()];
+            let mut chars = line.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => fields.last_mut()?.extend(chars.next()),
+                    ':' if fields.len() < 5 => fields.push(String::new()),
+                    c => fields.last_mut()?.push(c),
+                }
+            }
+            let [host, port, database, user, password] = <[String; 5]>::try_from(fields).ok()?;
+            Some(PgpassEntry {
+                host,
+                port,
+                database,
+                user,
+                password,
+            })
+        })
+        .collect()
+}
+
+/// The sections of an ini style file, in order, with their `key=value` pairs.
+/// Keys are lowercased and `-` is treated like `_`, as both clients do.
+fn parse_ini(content: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut sections: Vec<(String, Vec<(String, String)>)> = vec![];
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((section.trim().to_string(), vec![]));
+            continue;
+        }
+        let (Some((_, values)), Some((key, value))) = (sections.last_mut(), line.split_once('='))
+        else {
+            continue;
+        };
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+            .unwrap_or(value);
+        values.push((
+            key.trim().to_ascii_lowercase().replace('-', "_"),
+            value.to_string(),
+        ));
+    }
+    sections
+}
+
+fn parse_pg_service(content: &str, pgpass: &[PgpassEntry]) -> Vec<NewConnectionInfo> {
+    parse_ini(content)
+        .into_iter()
+        .map(|(service, values)| {
+            let mut connection = postgres(service);
+            let mut options = vec![];
+            for (key, value) in values {
+                match key.as_str() {
+                    "host" | "hostaddr" => connection.host = value,
+                    "port" => connection.port = value.parse().unwrap_or(5432),
+                    "dbname" => connection.database = value,
+                    "user" => connection.user = value,
+                    "password" => connection.password = value,
+                    _ => options.push((key, value)),
+                }
+            }
+            connection.tls = TlsConfig::take_from_options(&mut options).unwrap_or_default();
+            if connection.password.is_empty() {
+                if let Some(entry) = pgpass.iter().find(|entry| entry.matches(&connection)) {
+                    connection.password = entry.password.clone();
+                }
+            }
+            connection
+        })
+        .collect()
+}
+
+fn parse_my_cnf(content: &str) -> Vec<NewConnectionInfo> {
+    parse_ini(content)
+        .into_iter()
+        // Other sections configure the server and tools other than clients
+        .filter(|(section, _)| section.starts_with("client") || section == "mysql")
+        .filter_map(|(section, values)| {
+            let mut connection = NewConnectionInfo {
+                name: format!("my.cnf [{}]", section),
+                host: "localhost".into(),
+                port: 3306,
+                user: String::new(),
+                password: String::new(),
+                database: String::new(),
+                data_files: vec![],
+                database_type: DatabaseType::Mysql,
+                options: vec![],
+                tls: TlsConfig::default(),
+                ssh: None,
+                read_only: false,
+                environment: None,
+                group: None,
+                advanced: AdvancedSettings::default(),
+            };
+            let mut options = vec![];
+            for (key, value) in values {
+                match key.as_str() {
+                    "host" => connection.host = value,
+                    "port" => connection.port = value.parse().unwrap_or(3306),
+                    "user" => connection.user = value,
+                    "password" => connection.password = value,
+                    "database" => connection.database = value,
+                    "ssl_mode" | "ssl_ca" | "ssl_cert" | "ssl_key" => {
+                        options.push((key.replace('_', "-"), value))
+                    }
+                    _ => {}
+                }
+            }
+            connection.tls = TlsConfig::take_from_options(&mut options).unwrap_or_default();
+            // A section with nothing but client settings isn't a connection
+            (!connection.user.is_empty()).then_some(connection)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pgpass_escaped_colons() {
+        let entries = parse_pgpass("# comment\ndb\\:host:5432:app:me:pa\\:ss\\\\word\n");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].host, "db:host");
+        assert_eq!(entries[0].password, "pa:ss\\word");
+    }
+
+    #[test]
+    fn pgpass_colons_in_password() {
+        let entries = parse_pgpass("localhost:5432:app:me:a:b:c");
+        assert_eq!(entries[0].password, "a:b:c");
+    }
+
+    #[test]
+    fn pgpass_wildcards() {
+        let entries = parse_pgpass("*:5432:app:me:secret\nlocalhost:*:*:me:other\n");
+        let mut connection = postgres("test".into());
+        connection.host = "anywhere".into();
+        connection.database = "app".into();
+        connection.user = "me".into();
+        assert!(entries[0].matches(&connection));
+        connection.port = 5433;
+        assert!(!entries[0].matches(&connection));
+
+        let mut connections = entries.into_iter().map(PgpassEntry::into_connection);
+        // A wildcard host doesn't describe a connection
+        assert!(connections.next().unwrap().is_none());
+        let connection = connections.next().unwrap().unwrap();
+        assert_eq!(connection.database, "postgres");
+        assert_eq!(connection.port, 5432);
+        assert_eq!(connection.password, "other");
+    }
+
+    #[test]
+    fn pg_service_password_from_pgpass() {
+        let pgpass = parse_pgpass("db.example.com:5432:app:me:secret\n");
+        let services = parse_pg_service(
+            "[prod]\nhost=db.example.com\ndbname=app\nuser=me\nsslmode=require\n\n[other]\nhost=elsewhere\nuser=me\n",
+            &pgpass,
+        );
+        assert_eq!(services.len(), 2);
+        assert_eq!(services[0].name, "prod");
+        assert_eq!(services[0].password, "secret");
+        assert_eq!(services[0].tls.mode, crate::data::TlsMode::Require);
+        assert_eq!(services[1].password, "");
+    }
+
+    #[test]
+    fn my_cnf_sections() {
+        let connections = parse_my_cnf(
+            "[mysqld]\nuser=mysql\n\n[client]\nport=3307\n\n[client-prod]\nhost = db\nuser = \"me\"\npassword='secret'\ndatabase=app\n",
+        );
+        // The server section is skipped, and so is [client] for lack of a user
+        assert_eq!(connections.len(), 1);
+        let connection = &connections[0];
+        assert_eq!(connection.name, "my.cnf [client-prod]");
+        assert_eq!(connection.host, "db");
+        assert_eq!(connection.port, 3306);
+        assert_eq!(connection.user, "me");
+        assert_eq!(connection.password, "secret");
+        assert_eq!(connection.database, "app");
+    }
+}
@@ -1,9 +1,23 @@
 pub mod app;
+pub mod batch;
+pub mod cli;
+pub mod config;
+pub mod connection_test;
 pub mod data;
 pub mod errors;
 pub mod events;
+pub mod files;
+pub mod health;
+pub mod import;
+pub mod notices;
+pub mod pages;
+pub mod query;
 pub mod render;
+pub mod results;
 pub mod screens;
+pub mod secrets;
+pub mod sql;
 pub mod term;
 pub mod theme;
+pub mod tunnel;
 pub mod widget;
@@ -1,11 +1,32 @@
-use color_eyre::Result;
-use dbterm::{app::Runtime, errors, term};
+use clap::Parser;
+use color_eyre::{eyre::eyre, Result};
+use dbterm::{app::Runtime, batch, cli::Cli, errors, notices, term};
 
 async fn run() -> Result<()> {
     errors::init_hooks()?;
+    notices::init()?;
+    // Parse before taking over the terminal so usage errors print normally
+    let cli = Cli::parse();
+    let connect = cli.connect_command()?;
+
+    if let Some(sql) = cli.batch_query()? {
+        let connect = connect.ok_or_else(|| {
+            eyre!("Running SQL needs a connection: pass --connection, a URL or DATABASE_URL")
+        })?;
+        return batch::run(connect, sql, cli.format, cli.continue_on_error).await;
+    }
+
+    let query = cli.query_text()?;
+
     let terminal = term::init()?;
     // create app and run it
-    let runtime = Runtime::new(terminal);
+    let mut runtime = Runtime::new(terminal);
+    if let Some(command) = connect {
+        runtime = runtime.connect(command);
+    }
+    if let Some(query) = query {
+        runtime = runtime.query(&query);
+    }
 
     runtime.run().await?;
 
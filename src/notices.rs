@@ -0,0 +1,97 @@
+//! Notices the server sends along with a query's results, like the ones from
+//! Postgres' `raise notice`. sqlx only logs them, so they're picked out of its
+//! log events, and belong to the query task that was running when they were
+//! logged.
+
+use std::{cell::RefCell, fmt, future::Future};
+
+use color_eyre::Result;
+use tracing::{
+    field::{Field, Visit},
+    subscriber::Interest,
+    Event, Level, Metadata, Subscriber,
+};
+use tracing_subscriber::{
+    layer::{Context, Layer, SubscriberExt},
+    registry,
+};
+
+const NOTICE_TARGET: &str = "sqlx::postgres::notice";
+
+tokio::task_local! {
+    static NOTICES: RefCell<Vec<String>>;
+}
+
+/// Starts picking notices out of sqlx's log events
+pub fn init() -> Result<()> {
+    tracing::subscriber::set_global_default(registry().with(NoticeLayer))?;
+    Ok(())
+}
+
+/// Runs `future`, collecting the notices logged as it runs for [`take`]
+pub async fn scope<F: Future>(future: F) -> F::Output {
+    NOTICES.scope(RefCell::new(vec![]), future).await
+}
+
+/// The notices collected in the current [`scope`] since the last call
+pub fn take() -> Vec<String> {
+    NOTICES
+        .try_with(|notices| notices.take())
+        .unwrap_or_default()
+}
+
+struct NoticeLayer;
+
+impl<S: Subscriber> Layer<S> for NoticeLayer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if metadata.target() == NOTICE_TARGET {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        metadata.target() == NOTICE_TARGET
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut message = Message(None);
+        event.record(&mut message);
+        let Some(message) = message.0 else {
+            return;
+        };
+        // sqlx maps the notice's severity to a log level, this maps it back
+        let severity = match *event.metadata().level() {
+            Level::ERROR => "ERROR",
+            Level::WARN => "WARNING",
+            Level::INFO => "NOTICE",
+            Level::DEBUG => "DEBUG",
+            Level::TRACE => "INFO",
+        };
+        NOTICES
+            .try_with(|notices| {
+                notices
+                    .borrow_mut()
+                    .push(format!("{}: {}", severity, message))
+            })
+            .ok();
+    }
+}
+
+/// The `message` field of an event
+struct Message(Option<String>);
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Query results fetched a page at a time, so a query that returns millions of
+//! rows shows its first ones right away instead of filling up memory.
+
+use std::{future::Future, time::Instant};
+
+use futures::{pin_mut, Stream, TryStreamExt};
+use sea_orm::{DbErr, QueryResult};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    notices,
+    render::RenderEvent,
+    results::{Column, ResultSet, Summary},
+};
+
+/// Rows fetched per page
+pub const PAGE_SIZE: usize = 500;
+
+/// The end of a running query its rows are sent out of
+pub struct Pages {
+    /// Asks for the next page. Without it, every page is sent without waiting
+    /// to be asked.
+    more_rx: Option<UnboundedReceiver<()>>,
+    render_tx: UnboundedSender<RenderEvent>,
+    /// The statement of the script the rows are from
+    statement: usize,
+    /// Whether pages past the first wait to be asked for. Only the last
+    /// statement's do, so the ones before it don't hold up the rest of the
+    /// script.
+    wait: bool,
+    /// Statements reported with [`Pages::send_failed`]
+    failed: usize,
+    /// The summary of a statement that returned no rows, held until
+    /// [`Pages::send_empty`] has its columns
+    empty: Option<Summary>,
+}
+
+impl Pages {
+    pub fn new(
+        more_rx: Option<UnboundedReceiver<()>>,
+        render_tx: UnboundedSender<RenderEvent>,
+    ) -> Self {
+        Self {
+            wait: more_rx.is_some(),
+            more_rx,
+            render_tx,
+            statement: 0,
+            failed: 0,
+            empty: None,
+        }
+    }
+
+    /// Sends what comes next as the results of `statement`
+    pub fn start_statement(&mut self, statement: usize, last: bool) {
+        self.statement = statement;
+        self.wait = last && self.more_rx.is_some();
+        self.empty = None;
+    }
+
+    pub fn statement(&self) -> usize {
+        self.statement
+    }
+
+    pub fn failed(&self) -> usize {
+        self.failed
+    }
+
+    /// Sends the first page of `rows` as a [`RenderEvent::QueryResult`],
+    /// timed from `started`, and every next one as a
+    /// [`RenderEvent::QueryRows`]. Stops once the rows run out or nobody is
+    /// left to ask for more. Rows carry their columns, so if there are none
+    /// the result waits for [`Pages::send_empty`].
+    pub async fn send<S>(&mut self, rows: S, started: Instant) -> Result<(), DbErr>
+    where
+        S: Stream<Item = Result<QueryResult, DbErr>>,
+    {
+        pin_mut!(rows);
+        let mut first = true;
+        loop {
+            let page = next_page(&mut rows).await?;
+            // A full page may be followed by an empty one, but telling would
+            // mean fetching ahead
+            let more = page.len() == PAGE_SIZE;
+            let event = if first {
+                let summary = Summary {
+                    duration: started.elapsed(),
+                    rows_affected: None,
+                    notices: notices::take(),
+                };
+                if page.is_empty() {
+                    self.empty = Some(summary);
+                    return Ok(());
+                }
+                RenderEvent::QueryResult {
+                    statement: self.statement,
+                    result: ResultSet::from_rows(&page),
+                    more,
+                    summary,
+                }
+            } else {
+                RenderEvent::QueryRows {
+                    statement: self.statement,
+                    rows: ResultSet::from_rows(&page).rows,
+                    more,
+                }
+            };
+            first = false;
+            self.render_tx.send(event).ok();
+            if !more {
+                return Ok(());
+            }
+            if let (true, Some(more_rx)) = (self.wait, &mut self.more_rx) {
+                if more_rx.recv().await.is_none() {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Sends the count `done` comes back with, for statements that don't
+    /// return rows, as a [`RenderEvent::QueryResult`] without any
+    pub async fn send_rows_affected<F>(&self, done: F, started: Instant) -> Result<(), DbErr>
+    where
+        F: Future<Output = Result<u64, DbErr>>,
+    {
+        let rows_affected = done.await?;
+        self.render_tx
+            .send(RenderEvent::QueryResult {
+                statement: self.statement,
+                result: ResultSet::default(),
+                more: false,
+                summary: Summary {
+                    duration: started.elapsed(),
+                    rows_affected: Some(rows_affected),
+                    notices: notices::take(),
+                },
+            })
+            .ok();
+        Ok(())
+    }
+
+    /// Whether the statement returned no rows, and its result is waiting for
+    /// [`Pages::send_empty`]
+    pub fn is_empty(&self) -> bool {
+        self.empty.is_some()
+    }
+
+    /// Sends the result of a statement that returned no rows, with the
+    /// columns it would have had
+    pub fn send_empty(&mut self, columns: Vec<Column>) {
+        let Some(summary) = self.empty.take() else {
+            return;
+        };
+        self.render_tx
+            .send(RenderEvent::QueryResult {
+                statement: self.statement,
+                result: ResultSet {
+                    columns,
+                    rows: vec![],
+                },
+                more: false,
+                summary,
+            })
+            .ok();
+    }
+
+    /// Sends notices that came in after the rows did, or keeps them with a
+    /// result that's waiting for its columns
+    pub fn send_notices(&mut self, notices: Vec<String>) {
+        if let Some(summary) = &mut self.empty {
+            summary.notices.extend(notices);
+        } else if !notices.is_empty() {
+            self.render_tx
+                .send(RenderEvent::Notices {
+                    statement: self.statement,
+                    notices,
+                })
+                .ok();
+        }
+    }
+
+    /// Sends why the statement failed, for scripts that go on past it or
+    /// show it next to the statements that ran
+    pub fn send_failed(&mut self, error: &DbErr) {
+        self.failed += 1;
+        self.render_tx
+            .send(RenderEvent::StatementFailed {
+                statement: self.statement,
+                error: error.to_string(),
+            })
+            .ok();
+    }
+}
+
+async fn next_page<S>(rows: &mut S) -> Result<Vec<QueryResult>, DbErr>
+where
+    S: Stream<Item = Result<QueryResult, DbErr>> + Unpin,
+{
+    let mut page = Vec::with_capacity(PAGE_SIZE);
+    while page.len() < PAGE_SIZE {
+        match rows.try_next().await? {
+            Some(row) => page.push(row),
+            None => break,
+        }
+    }
+    Ok(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn empty_results_wait_for_their_columns() {
+        let (render_tx, mut render_rx) = unbounded_channel();
+        let mut pages = Pages::new(None, render_tx);
+        pages.start_statement(0, true);
+        pages.send(stream::empty(), Instant::now()).await.unwrap();
+        assert!(pages.is_empty());
+        assert!(render_rx.try_recv().is_err());
+
+        pages.send_notices(vec!["NOTICE: nothing to do".to_string()]);
+        let columns = vec![Column {
+            name: "id".to_string(),
+            type_name: "INT8".to_string(),
+        }];
+        pages.send_empty(columns.clone());
+        assert!(!pages.is_empty());
+        match render_rx.try_recv() {
+            Ok(RenderEvent::QueryResult {
+                result, summary, ..
+            }) => {
+                assert_eq!(result.columns, columns);
+                assert!(result.rows.is_empty());
+                assert_eq!(summary.notices, ["NOTICE: nothing to do"]);
+            }
+            _ => panic!("expected the statement's result"),
+        }
+    }
+}
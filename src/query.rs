@@ -0,0 +1,493 @@
+//! Queries run as tasks of their own, so the store keeps handling commands
+//! while one runs and can cancel it.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use futures::{Future, TryFutureExt, TryStreamExt};
+use sea_orm::{
+    sqlx::{self, Connection, MySqlConnection, MySqlPool, PgConnection, PgPool, Row, SqlitePool},
+    DatabaseConnection, DbErr, QueryResult, RuntimeErr,
+};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedSender},
+    oneshot,
+};
+
+use crate::{
+    data::{conn_err, DatabaseType},
+    health, notices,
+    pages::Pages,
+    render::RenderEvent,
+    results, sql,
+};
+
+/// How long cancelling on the server gets before the query is stopped anyway
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Virtual machine instructions SQLite runs between checks for an interrupt
+const SQLITE_PROGRESS_OPS: i32 = 1000;
+
+/// Runs `sql` on `connection`, sending its rows, or the count of rows it
+/// affected, through `pages` until it's done or stopped. A macro since every
+/// backend's connection is a type of its own.
+///
+/// Sent without arguments, statements go over the simple query protocol,
+/// which returns values as text rather than in each type's binary encoding.
+/// Types there's no decoder for, like Postgres arrays and intervals, then
+/// still read as text.
+macro_rules! run_statement {
+    ($connection:ident, $sql:expr, $database_type:expr, $pages:expr, $stop_rx:expr) => {{
+        let started = Instant::now();
+        if sql::returns_rows($sql, $database_type) {
+            let rows = sqlx::Executor::fetch(&mut *$connection, $sql.as_str())
+                .map_ok(QueryResult::from)
+                .map_err(query_err);
+            until_stopped($pages.send(rows, started), $stop_rx).await
+        } else {
+            let done = sqlx::Executor::execute(&mut *$connection, $sql.as_str())
+                .map_ok(|done| done.rows_affected())
+                .map_err(query_err);
+            until_stopped($pages.send_rows_affected(done, started), $stop_rx).await
+        }
+    }};
+}
+
+/// Sends the result of `sql` if it returned no rows, with the columns the
+/// database describes it as having. Only once the statement's warnings are
+/// in, since describing prepares a statement, which clears them on MySQL.
+macro_rules! send_empty {
+    ($connection:ident, $sql:expr, $pages:expr) => {{
+        if $pages.is_empty() {
+            let columns = sqlx::Executor::describe(&mut *$connection, $sql.as_str())
+                .await
+                .map(|describe| results::columns(describe.columns()))
+                .unwrap_or_default();
+            $pages.send_empty(columns);
+        }
+    }};
+}
+
+/// Runs the statements of `script` on `connection` one after the other,
+/// running `after` once each is done. Stops at the first one that fails,
+/// unless the script continues on error and it didn't lose the connection.
+/// A lone statement's failure is left for the store to report.
+macro_rules! run_script {
+    ($connection:ident, $script:expr, $pages:expr, $stop_rx:expr, $after:expr) => {{
+        let statements = &$script.statements;
+        let mut outcome = Outcome::Done;
+        for (index, statement) in statements.iter().enumerate() {
+            $pages.start_statement(index, index + 1 == statements.len());
+            let ran = run_statement!(
+                $connection,
+                statement,
+                $script.database_type,
+                $pages,
+                $stop_rx
+            );
+            $pages.send_notices(notices::take());
+            match ran {
+                Outcome::Done => {
+                    $after;
+                    send_empty!($connection, statement, $pages);
+                }
+                Outcome::Failed(e)
+                    if $script.continue_on_error && !health::is_connection_error(&e) =>
+                {
+                    $pages.send_failed(&e);
+                }
+                Outcome::Failed(e) => {
+                    if statements.len() > 1 {
+                        $pages.send_failed(&e);
+                    }
+                    outcome = Outcome::Failed(e);
+                    break;
+                }
+                Outcome::Cancelled => {
+                    outcome = Outcome::Cancelled;
+                    break;
+                }
+            }
+        }
+        outcome
+    }};
+}
+
+/// Rolls back the transaction `script` may have left open, so its connection
+/// doesn't go back to the pool in the middle of one, or in one that failed.
+/// Whether the connection is fit to reuse.
+macro_rules! roll_back {
+    ($connection:ident, $script:expr) => {{
+        !$script.opens_transaction()
+            || sqlx::Executor::execute(&mut *$connection, "rollback")
+                .await
+                .is_ok()
+    }};
+}
+
+/// The session a query runs in, the way the server can be told to cancel it
+#[derive(Clone)]
+enum Session {
+    /// The backend's process id
+    Postgres(i32),
+    /// The connection id
+    Mysql(u64),
+    /// Checked by SQLite as it runs the query, which is interrupted once it's set
+    Sqlite(Arc<AtomicBool>),
+}
+
+impl Session {
+    /// Postgres and MySQL are told over a connection of its own rather than
+    /// one from the pool, which the query may have taken the last of
+    async fn cancel(&self, pool: &DatabaseConnection) -> Result<(), DbErr> {
+        match self {
+            Session::Postgres(pid) => {
+                let options = pool.get_postgres_connection_pool().connect_options();
+                let mut connection = PgConnection::connect_with(&options)
+                    .await
+                    .map_err(conn_err)?;
+                let cancelled = sqlx::query("select pg_cancel_backend($1)")
+                    .bind(pid)
+                    .execute(&mut connection)
+                    .await
+                    .map_err(query_err);
+                connection.close().await.ok();
+                cancelled?;
+            }
+            Session::Mysql(id) => {
+                let options = pool.get_mysql_connection_pool().connect_options();
+                let mut connection = MySqlConnection::connect_with(&options)
+                    .await
+                    .map_err(conn_err)?;
+                let cancelled =
+                    sqlx::Executor::execute(&mut connection, format!("kill query {}", id).as_str())
+                        .await
+                        .map_err(query_err);
+                connection.close().await.ok();
+                cancelled?;
+            }
+            Session::Sqlite(interrupted) => interrupted.store(true, Ordering::Relaxed),
+        }
+        Ok(())
+    }
+}
+
+/// The session of the query, once it has one, and not anymore once the
+/// connection is handed back
+type SessionSlot = Arc<Mutex<Option<Session>>>;
+
+/// The statements of what was typed in, run in order on the same connection
+pub struct Script {
+    pub statements: Vec<String>,
+    /// Whether the statements after a failing one still run
+    pub continue_on_error: bool,
+    /// What the statements are lexed as
+    pub database_type: DatabaseType,
+}
+
+impl Script {
+    fn opens_transaction(&self) -> bool {
+        self.statements
+            .iter()
+            .any(|statement| sql::begins_transaction(statement, self.database_type))
+    }
+
+    /// What's left to run after losing the connection at `statement`. A
+    /// transaction that was open is rolled back along with the connection,
+    /// so scripts with one start over rather than finishing it half applied
+    /// outside of it.
+    fn retry_from(&self, statement: usize) -> String {
+        let start = if self.opens_transaction() {
+            0
+        } else {
+            statement
+        };
+        self.statements[start..].join(";\n")
+    }
+}
+
+/// What a query runs on
+pub struct QueryTarget {
+    pub connection_id: usize,
+    pub pool: DatabaseConnection,
+    pub database_type: DatabaseType,
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    Done,
+    Cancelled,
+    Failed(DbErr),
+}
+
+/// Sent to the store once a query's task is over
+pub struct Finished {
+    /// See [`RunningQuery::id`]
+    pub id: u64,
+    pub connection_id: usize,
+    /// What's left to retry if the statement it ended on lost the
+    /// connection, see [`Script::retry_from`]
+    pub sql: String,
+    /// Statements in the script
+    pub statements: usize,
+    /// The statement it ended on
+    pub statement: usize,
+    /// Statements that failed, including one that stopped the script
+    pub failed: usize,
+    pub outcome: Outcome,
+}
+
+/// The store's handle on a query's task. The task keeps running while its
+/// rows are paged through.
+pub struct RunningQuery {
+    /// Counts up with every query, to tell a finished query from the one that
+    /// replaced it
+    pub id: u64,
+    pub connection_id: usize,
+    pool: DatabaseConnection,
+    session: SessionSlot,
+    more_tx: UnboundedSender<()>,
+    stop_tx: Option<oneshot::Sender<()>>,
+}
+
+impl RunningQuery {
+    /// Runs `script` on a connection of its own from the target's pool. Rows
+    /// go out through `render_tx`, a page at a time unless `paged` is off,
+    /// and [`Finished`] through `done_tx` once the task is over.
+    pub fn spawn(
+        id: u64,
+        script: Script,
+        target: QueryTarget,
+        paged: bool,
+        render_tx: UnboundedSender<RenderEvent>,
+        done_tx: UnboundedSender<Finished>,
+    ) -> Self {
+        let (more_tx, more_rx) = unbounded_channel();
+        let (stop_tx, stop_rx) = oneshot::channel();
+        let session = SessionSlot::default();
+        let running = Self {
+            id,
+            connection_id: target.connection_id,
+            pool: target.pool.clone(),
+            session: session.clone(),
+            more_tx,
+            stop_tx: Some(stop_tx),
+        };
+        let mut pages = Pages::new(paged.then_some(more_rx), render_tx);
+        tokio::spawn(async move {
+            let outcome = notices::scope(run(&target, &script, &mut pages, &session, stop_rx))
+                .await
+                .unwrap_or_else(Outcome::Failed);
+            let statement = pages.statement();
+            done_tx
+                .send(Finished {
+                    id,
+                    connection_id: target.connection_id,
+                    sql: script.retry_from(statement),
+                    statements: script.statements.len(),
+                    statement,
+                    failed: pages.failed(),
+                    outcome,
+                })
+                .ok();
+        });
+        running
+    }
+
+    /// Asks for the next page of rows
+    pub fn fetch_rows(&self) {
+        self.more_tx.send(()).ok();
+    }
+
+    /// Cancels the query on the server, then stops its task. Connections
+    /// that were stopped mid-query are closed rather than reused.
+    pub fn cancel(&mut self) {
+        let Some(stop_tx) = self.stop_tx.take() else {
+            return;
+        };
+        let session = self.session.lock().unwrap().clone();
+        let pool = self.pool.clone();
+        tokio::spawn(async move {
+            if let Some(session) = session {
+                tokio::time::timeout(CANCEL_TIMEOUT, session.cancel(&pool))
+                    .await
+                    .ok();
+            }
+            stop_tx.send(()).ok();
+        });
+    }
+}
+
+async fn run(
+    target: &QueryTarget,
+    script: &Script,
+    pages: &mut Pages,
+    session: &SessionSlot,
+    mut stop_rx: oneshot::Receiver<()>,
+) -> Result<Outcome, DbErr> {
+    let pool = &target.pool;
+    match target.database_type {
+        DatabaseType::Postgres => {
+            let pool = pool.get_postgres_connection_pool();
+            run_postgres(pool, script, pages, session, &mut stop_rx).await
+        }
+        DatabaseType::Mysql => {
+            let pool = pool.get_mysql_connection_pool();
+            run_mysql(pool, script, pages, session, &mut stop_rx).await
+        }
+        DatabaseType::Sqlite | DatabaseType::Files => {
+            let pool = pool.get_sqlite_connection_pool();
+            run_sqlite(pool, script, pages, session, &mut stop_rx).await
+        }
+    }
+}
+
+async fn run_postgres(
+    pool: &PgPool,
+    script: &Script,
+    pages: &mut Pages,
+    session: &SessionSlot,
+    stop_rx: &mut oneshot::Receiver<()>,
+) -> Result<Outcome, DbErr> {
+    let mut connection = pool.acquire().await.map_err(conn_err)?;
+    let pid = sqlx::query_scalar::<_, i32>("select pg_backend_pid()")
+        .fetch_one(&mut *connection)
+        .await
+        .map_err(query_err)?;
+    *session.lock().unwrap() = Some(Session::Postgres(pid));
+    let outcome = run_script!(connection, script, pages, stop_rx, {});
+    session.lock().unwrap().take();
+    if matches!(outcome, Outcome::Cancelled) || !roll_back!(connection, script) {
+        connection.close().await.ok();
+    }
+    Ok(outcome)
+}
+
+async fn run_mysql(
+    pool: &MySqlPool,
+    script: &Script,
+    pages: &mut Pages,
+    session: &SessionSlot,
+    stop_rx: &mut oneshot::Receiver<()>,
+) -> Result<Outcome, DbErr> {
+    let mut connection = pool.acquire().await.map_err(conn_err)?;
+    let id = sqlx::query_scalar::<_, u64>("select connection_id()")
+        .fetch_one(&mut *connection)
+        .await
+        .map_err(query_err)?;
+    *session.lock().unwrap() = Some(Session::Mysql(id));
+    // Each statement's warnings are only kept until the next one runs
+    let outcome = run_script!(connection, script, pages, stop_rx, {
+        pages.send_notices(warnings(&mut connection).await)
+    });
+    session.lock().unwrap().take();
+    if matches!(outcome, Outcome::Cancelled) || !roll_back!(connection, script) {
+        connection.close().await.ok();
+    }
+    Ok(outcome)
+}
+
+/// SQLite is interrupted from a progress handler, which leaves the connection
+/// fit to reuse. That matters for data files, whose only connection holds
+/// the data, which is why it's kept even if rolling back fails. That mostly
+/// means there was no transaction left to roll back.
+async fn run_sqlite(
+    pool: &SqlitePool,
+    script: &Script,
+    pages: &mut Pages,
+    session: &SessionSlot,
+    stop_rx: &mut oneshot::Receiver<()>,
+) -> Result<Outcome, DbErr> {
+    let mut connection = pool.acquire().await.map_err(conn_err)?;
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    connection
+        .lock_handle()
+        .await
+        .map_err(query_err)?
+        .set_progress_handler(SQLITE_PROGRESS_OPS, move || !flag.load(Ordering::Relaxed));
+    *session.lock().unwrap() = Some(Session::Sqlite(interrupted));
+    let outcome = run_script!(connection, script, pages, stop_rx, {});
+    session.lock().unwrap().take();
+    if let Ok(mut handle) = connection.lock_handle().await {
+        handle.remove_progress_handler();
+    }
+    if script.opens_transaction() {
+        sqlx::Executor::execute(&mut *connection, "rollback")
+            .await
+            .ok();
+    }
+    Ok(outcome)
+}
+
+/// Waits for `sent` unless the query is stopped first
+async fn until_stopped<F>(sent: F, stop_rx: &mut oneshot::Receiver<()>) -> Outcome
+where
+    F: Future<Output = Result<(), DbErr>>,
+{
+    tokio::select! {
+        result = sent => match result {
+            Ok(()) => Outcome::Done,
+            Err(e) => Outcome::Failed(e),
+        },
+        _ = stop_rx => Outcome::Cancelled,
+    }
+}
+
+/// MySQL keeps the warnings of the last statement for them to be asked for.
+/// Asked without preparing a statement, which would clear them.
+async fn warnings(connection: &mut MySqlConnection) -> Vec<String> {
+    let Ok(rows) = sqlx::Executor::fetch_all(&mut *connection, "show warnings").await else {
+        return vec![];
+    };
+    rows.iter()
+        .filter_map(|row| {
+            let level = row.try_get_unchecked::<String, _>(0).ok()?;
+            let message = row.try_get_unchecked::<String, _>(2).ok()?;
+            Some(format!("{}: {}", level.to_uppercase(), message))
+        })
+        .collect()
+}
+
+/// Query errors the way sea-orm reports them, which is what [`crate::health`]
+/// looks for
+fn query_err(error: sqlx::Error) -> DbErr {
+    DbErr::Query(RuntimeErr::SqlxError(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn script(statements: &[&str]) -> Script {
+        Script {
+            statements: statements.iter().map(|s| s.to_string()).collect(),
+            continue_on_error: false,
+            database_type: DatabaseType::Postgres,
+        }
+    }
+
+    #[test]
+    fn retry_continues_from_the_lost_statement() {
+        let script = script(&["update a set x = 1", "update b set x = 1", "select 1"]);
+        assert_eq!(script.retry_from(1), "update b set x = 1;\nselect 1");
+    }
+
+    #[test]
+    fn retry_restarts_transactions() {
+        let script = script(&[
+            "begin",
+            "update a set x = 1",
+            "update b set x = 1",
+            "commit",
+        ]);
+        assert_eq!(
+            script.retry_from(2),
+            "begin;\nupdate a set x = 1;\nupdate b set x = 1;\ncommit"
+        );
+    }
+}
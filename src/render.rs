@@ -11,16 +11,77 @@ use tokio::{
     task::JoinHandle,
 };
 
-use crate::{app::App, data::Data, widget::AppWidget};
+use crate::{
+    app::App,
+    connection_test::ConnectionTest,
+    data::{Data, DatabaseType, Environment, NewConnectionInfo},
+    results::{Cell, ResultSet, Summary},
+    widget::AppWidget,
+};
 
 pub enum RenderEvent {
     Draw,
     StatusMessage(Status),
+    /// A query was sent off, and can be cancelled until it's answered. Its
+    /// statements run one after the other, and the events about their
+    /// results are told apart by their index.
+    QueryStarted {
+        statements: Vec<String>,
+    },
+    /// The query's task is over, whether it ran out of rows, failed or was
+    /// cancelled
+    QueryFinished,
+    /// The first page of a statement's rows, and whether there are more.
+    /// Statements that don't return rows come with an empty result and the
+    /// count of rows they affected in the summary.
     QueryResult {
-        headers: Vec<String>,
-        rows: Vec<Vec<String>>,
+        statement: usize,
+        result: ResultSet,
+        more: bool,
+        summary: Summary,
+    },
+    /// The next page of a statement's rows, asked for with
+    /// [`AppCommand::FetchRows`](crate::data::AppCommand::FetchRows) if it's
+    /// the last one
+    QueryRows {
+        statement: usize,
+        rows: Vec<Vec<Cell>>,
+        more: bool,
+    },
+    /// Notices or warnings the server sent after a statement's first page
+    Notices {
+        statement: usize,
+        notices: Vec<String>,
+    },
+    /// A statement of a script failed
+    StatementFailed {
+        statement: usize,
+        error: String,
     },
-    Connected,
+    /// A connection was opened or switched to, and is now the one queries go to
+    Connected {
+        id: usize,
+        name: String,
+        database_type: DatabaseType,
+        read_only: bool,
+        environment: Option<Environment>,
+    },
+    /// The connection was deleted, or edited and opened afresh
+    Closed {
+        id: usize,
+    },
+    /// The active connection stopped answering and is being reconnected
+    ConnectionLost {
+        name: String,
+    },
+    Reconnected {
+        name: String,
+        /// Whether a query failed while the connection was lost
+        can_retry: bool,
+    },
+    /// Connections found in other clients' config files, for the user to pick from
+    ImportCandidates(Vec<NewConnectionInfo>),
+    ConnectionTested(ConnectionTest),
 }
 
 pub fn render_loop(
@@ -45,12 +106,58 @@ pub fn render_loop(
                 RenderEvent::StatusMessage(status) => {
                     app.set_status_message(status);
                 }
-                RenderEvent::Connected => {
-                    app.set_status_message(Status::Success("Connected".into()));
+                RenderEvent::Connected {
+                    id,
+                    name,
+                    database_type,
+                    read_only,
+                    environment,
+                } => {
+                    app.set_status_message(Status::Success(format!("Connected to {}", name)));
+                    app.set_active_connection(id, name, database_type, read_only, environment);
                     app.goto_main_screen();
                 }
-                RenderEvent::QueryResult { headers, rows } => {
-                    app.set_query_result(headers, rows);
+                RenderEvent::Closed { id } => {
+                    app.close_connection(id);
+                }
+                RenderEvent::ConnectionLost { name } => {
+                    app.set_connection_lost(name);
+                }
+                RenderEvent::Reconnected { name, can_retry } => {
+                    app.set_reconnected(name, can_retry);
+                }
+                RenderEvent::QueryStarted { statements } => {
+                    app.start_query(statements);
+                }
+                RenderEvent::QueryFinished => {
+                    app.finish_query();
+                }
+                RenderEvent::QueryResult {
+                    statement,
+                    result,
+                    more,
+                    summary,
+                } => {
+                    app.set_query_result(statement, result, more, summary);
+                }
+                RenderEvent::QueryRows {
+                    statement,
+                    rows,
+                    more,
+                } => {
+                    app.append_query_rows(statement, rows, more);
+                }
+                RenderEvent::Notices { statement, notices } => {
+                    app.add_query_notices(statement, notices);
+                }
+                RenderEvent::StatementFailed { statement, error } => {
+                    app.set_statement_failed(statement, error);
+                }
+                RenderEvent::ImportCandidates(candidates) => {
+                    app.show_import_candidates(candidates);
+                }
+                RenderEvent::ConnectionTested(result) => {
+                    app.set_connection_test(result);
                 }
             }
             draw!();
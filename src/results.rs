@@ -0,0 +1,272 @@
+//! Query results as they come back from the database. Columns keep the order
+//! the query selected them in, duplicate names included, and cells are
+//! decoded by the column's type instead of going through JSON.
+
+use std::{fmt, time::Duration};
+
+use sea_orm::{
+    prelude::{ChronoDate, ChronoDateTime, ChronoDateTimeUtc, ChronoTime, Decimal, Uuid},
+    sqlx::{
+        self, mysql::MySqlRow, postgres::types::Oid, postgres::PgRow, sqlite::SqliteRow,
+        ColumnIndex, Decode, Row, TypeInfo, ValueRef,
+    },
+    QueryResult,
+};
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    /// The type as the backend calls it, e.g. `INT8` or `VARCHAR`
+    pub type_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// Exact numbers, and unsigned integers too big for an `i64`
+    Decimal(Decimal),
+    Text(String),
+    Bytes(Vec<u8>),
+    Json(Value),
+    Uuid(Uuid),
+    Date(ChronoDate),
+    Time(ChronoTime),
+    Timestamp(ChronoDateTime),
+    TimestampTz(ChronoDateTimeUtc),
+}
+
+impl Cell {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Cell::Null)
+    }
+
+    /// Numbers and booleans stay JSON numbers and booleans, JSON columns are
+    /// nested as is and everything else becomes a string. Decimals are
+    /// strings too, so they don't lose precision.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Cell::Null => Value::Null,
+            Cell::Bool(value) => Value::Bool(*value),
+            Cell::Int(value) => Value::from(*value),
+            Cell::Float(value) => Value::from(*value),
+            Cell::Json(value) => value.clone(),
+            cell => Value::String(cell.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Null => write!(f, "NULL"),
+            Cell::Bool(value) => write!(f, "{}", value),
+            Cell::Int(value) => write!(f, "{}", value),
+            Cell::Float(value) => write!(f, "{}", value),
+            Cell::Decimal(value) => write!(f, "{}", value),
+            Cell::Text(value) => write!(f, "{}", value),
+            // The way Postgres prints bytea
+            Cell::Bytes(value) => {
+                write!(f, "\\x")?;
+                value.iter().try_for_each(|byte| write!(f, "{:02x}", byte))
+            }
+            Cell::Json(value) => write!(f, "{}", value),
+            Cell::Uuid(value) => write!(f, "{}", value),
+            Cell::Date(value) => write!(f, "{}", value),
+            Cell::Time(value) => write!(f, "{}", value),
+            Cell::Timestamp(value) => write!(f, "{}", value),
+            Cell::TimestampTz(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// What a statement reports besides its rows
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    /// Until the first page of rows came back, or the statement was done for
+    /// ones that don't return rows
+    pub duration: Duration,
+    /// Only for statements that don't return rows
+    pub rows_affected: Option<u64>,
+    /// Notices and warnings the server sent along, like `NOTICE: ...`
+    pub notices: Vec<String>,
+}
+
+impl Summary {
+    /// Milliseconds up to a second, seconds from there
+    pub fn timing(&self) -> String {
+        if self.duration < Duration::from_secs(1) {
+            format!("{} ms", self.duration.as_millis())
+        } else {
+            format!("{:.2} s", self.duration.as_secs_f64())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResultSet {
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl ResultSet {
+    /// The columns are read off the first row, so a query that returns no
+    /// rows has no columns either. Those are found with [`columns`] instead.
+    pub fn from_rows(results: &[QueryResult]) -> Self {
+        let columns = results
+            .first()
+            .map(|result| {
+                result
+                    .try_as_pg_row()
+                    .map(|row| columns(row.columns()))
+                    .or_else(|| result.try_as_mysql_row().map(|row| columns(row.columns())))
+                    .or_else(|| result.try_as_sqlite_row().map(|row| columns(row.columns())))
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default();
+        let rows = results
+            .iter()
+            .map(|result| {
+                result
+                    .try_as_pg_row()
+                    .map(|row| cells(row, pg_cell))
+                    .or_else(|| result.try_as_mysql_row().map(|row| cells(row, mysql_cell)))
+                    .or_else(|| {
+                        result
+                            .try_as_sqlite_row()
+                            .map(|row| cells(row, sqlite_cell))
+                    })
+                    .unwrap_or_default()
+            })
+            .collect();
+        Self { columns, rows }
+    }
+}
+
+/// The names and types of the columns of a row, or of the rows a statement
+/// would return as described by the database
+pub fn columns<C: sqlx::Column>(columns: &[C]) -> Vec<Column> {
+    columns
+        .iter()
+        .map(|column| Column {
+            name: sqlx::Column::name(column).to_string(),
+            type_name: sqlx::Column::type_info(column).name().to_string(),
+        })
+        .collect()
+}
+
+fn cells<R: Row>(row: &R, cell: fn(&R, usize) -> Option<Cell>) -> Vec<Cell>
+where
+    usize: ColumnIndex<R>,
+    for<'r> Vec<u8>: Decode<'r, R::Database>,
+{
+    (0..row.len())
+        .map(|i| {
+            let null = row.try_get_raw(i).map_or(true, |value| value.is_null());
+            if null {
+                return Cell::Null;
+            }
+            cell(row, i).unwrap_or_else(|| fallback(row, i))
+        })
+        .collect()
+}
+
+fn get<'r, R, T>(row: &'r R, i: usize) -> Option<T>
+where
+    R: Row,
+    T: Decode<'r, R::Database>,
+    usize: ColumnIndex<R>,
+{
+    row.try_get_unchecked(i).ok()
+}
+
+/// Types there's no decoder for are shown as text if their encoding is
+/// readable, and as bytes if it isn't
+fn fallback<R: Row>(row: &R, i: usize) -> Cell
+where
+    usize: ColumnIndex<R>,
+    for<'r> Vec<u8>: Decode<'r, R::Database>,
+{
+    let Some(bytes) = get::<R, Vec<u8>>(row, i) else {
+        return Cell::Null;
+    };
+    match String::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(|c| c.is_control() && !c.is_whitespace()) => Cell::Text(text),
+        Ok(text) => Cell::Bytes(text.into_bytes()),
+        Err(e) => Cell::Bytes(e.into_bytes()),
+    }
+}
+
+/// A `u64` only fits in an `Int` up to `i64::MAX`
+fn unsigned(value: u64) -> Cell {
+    i64::try_from(value)
+        .map(Cell::Int)
+        .unwrap_or_else(|_| Cell::Decimal(value.into()))
+}
+
+fn pg_cell(row: &PgRow, i: usize) -> Option<Cell> {
+    let type_info = sqlx::Column::type_info(&row.columns()[i]);
+    match type_info.name() {
+        "BOOL" => get(row, i).map(Cell::Bool),
+        "INT2" => get::<_, i16>(row, i).map(|value| Cell::Int(value.into())),
+        "INT4" => get::<_, i32>(row, i).map(|value| Cell::Int(value.into())),
+        "INT8" => get(row, i).map(Cell::Int),
+        "OID" => get::<_, Oid>(row, i).map(|oid| Cell::Int(oid.0.into())),
+        "FLOAT4" => get::<_, f32>(row, i).map(|value| Cell::Float(value.into())),
+        "FLOAT8" => get(row, i).map(Cell::Float),
+        "NUMERIC" => get(row, i).map(Cell::Decimal),
+        "TEXT" | "VARCHAR" | "BPCHAR" | "NAME" | "CITEXT" => get(row, i).map(Cell::Text),
+        "BYTEA" => get(row, i).map(Cell::Bytes),
+        "JSON" | "JSONB" => get(row, i).map(Cell::Json),
+        "UUID" => get(row, i).map(Cell::Uuid),
+        "DATE" => get(row, i).map(Cell::Date),
+        "TIME" => get(row, i).map(Cell::Time),
+        "TIMESTAMP" => get(row, i).map(Cell::Timestamp),
+        "TIMESTAMPTZ" => get(row, i).map(Cell::TimestampTz),
+        // Queries come back in the text format, where anything else, like
+        // arrays, intervals and network addresses, is how psql would print it
+        _ => get(row, i).map(Cell::Text),
+    }
+}
+
+fn mysql_cell(row: &MySqlRow, i: usize) -> Option<Cell> {
+    let type_info = sqlx::Column::type_info(&row.columns()[i]);
+    match type_info.name() {
+        "BOOLEAN" => get(row, i).map(Cell::Bool),
+        "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" => get(row, i).map(Cell::Int),
+        "TINYINT UNSIGNED" | "SMALLINT UNSIGNED" | "MEDIUMINT UNSIGNED" | "INT UNSIGNED"
+        | "BIGINT UNSIGNED" | "BIT" => get(row, i).map(unsigned),
+        "YEAR" => get::<_, u16>(row, i).map(|year| Cell::Int(year.into())),
+        "FLOAT" => get::<_, f32>(row, i).map(|value| Cell::Float(value.into())),
+        "DOUBLE" => get(row, i).map(Cell::Float),
+        "DECIMAL" => get(row, i).map(Cell::Decimal),
+        "CHAR" | "VARCHAR" | "TINYTEXT" | "TEXT" | "MEDIUMTEXT" | "LONGTEXT" | "ENUM" | "SET" => {
+            get(row, i).map(Cell::Text)
+        }
+        "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB" => {
+            get(row, i).map(Cell::Bytes)
+        }
+        "JSON" => get(row, i).map(Cell::Json),
+        "DATE" => get(row, i).map(Cell::Date),
+        "TIME" => get(row, i).map(Cell::Time),
+        "DATETIME" => get(row, i).map(Cell::Timestamp),
+        "TIMESTAMP" => get(row, i).map(Cell::TimestampTz),
+        _ => None,
+    }
+}
+
+/// SQLite columns can hold any type, so cells are decoded by the type of the
+/// value rather than the one the column was declared with
+fn sqlite_cell(row: &SqliteRow, i: usize) -> Option<Cell> {
+    let value = row.try_get_raw(i).ok()?;
+    match value.type_info().name() {
+        "INTEGER" => get(row, i).map(Cell::Int),
+        "REAL" => get(row, i).map(Cell::Float),
+        "TEXT" => get(row, i).map(Cell::Text),
+        "BLOB" => get(row, i).map(Cell::Bytes),
+        _ => None,
+    }
+}
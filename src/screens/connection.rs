@@ -6,7 +6,8 @@ use tokio::sync::mpsc::UnboundedSender;
 
 use self::connection_list::ConnectionList;
 use crate::{
-    data::{AppCommand, ConnectionInfo, Ctx},
+    connection_test::ConnectionTest,
+    data::{AppCommand, ConnectionInfo, Ctx, NewConnectionInfo},
     events::EventHandler,
     widget::AppWidget,
 };
@@ -17,30 +18,58 @@ use ratatui::{
     widgets::{Block, Borders, Paragraph, Wrap},
 };
 
-use dbterm_widgets::popup::Popup;
+use dbterm_widgets::{confirm::Confirm, picker::Picker, popup::Popup};
+
+const IMPORT_TITLE: &str = "Import Connections (space to select, enter to import)";
 
 #[derive(Debug, Default, PartialEq)]
 enum State {
     #[default]
     Home,
     NewConnection,
+    EditConnection(usize),
+    ConfirmDelete(usize),
+    Import,
 }
 
 #[derive(Debug)]
 pub(crate) struct ConnectionScreen {
     state: State,
     connections_list: ConnectionList,
-    new_connection_form: ConnectionInfoForm,
+    connection_form: ConnectionInfoForm,
+    delete_confirm: Confirm<'static>,
+    import_picker: Picker<'static, 'static, NewConnectionInfo>,
 }
 
 impl ConnectionScreen {
     pub fn new() -> Self {
         Self {
             state: State::Home,
-            new_connection_form: ConnectionInfoForm::new(),
+            connection_form: ConnectionInfoForm::new(),
             connections_list: ConnectionList::new(),
+            delete_confirm: Confirm::new("Delete Connection", ""),
+            import_picker: Picker::multiple(IMPORT_TITLE, Vec::<(String, _)>::new()),
         }
     }
+
+    pub fn show_import_candidates(&mut self, candidates: Vec<NewConnectionInfo>) {
+        let items = candidates
+            .into_iter()
+            .map(|c| {
+                let label = format!(
+                    "{} ({}@{}:{}/{})",
+                    c.name, c.user, c.host, c.port, c.database
+                );
+                (label, c)
+            })
+            .collect();
+        self.import_picker = Picker::multiple(IMPORT_TITLE, items);
+        self.state = State::Import;
+    }
+
+    pub fn set_connection_test(&mut self, result: ConnectionTest) {
+        self.connection_form.set_test_result(result);
+    }
 }
 
 impl EventHandler for ConnectionScreen {
@@ -51,47 +80,142 @@ impl EventHandler for ConnectionScreen {
         tx: &UnboundedSender<AppCommand>,
     ) -> Result<bool> {
         match self.state {
+            // Everything typed goes into the filter while it's open
+            State::Home if self.connections_list.is_filtering() => {
+                self.connections_list.handle_event(event, ctx, tx)
+            }
             State::Home => match event {
                 Event::Key(key_event) => {
+                    if key_event.code == KeyCode::Esc && self.connections_list.has_filter() {
+                        self.connections_list.clear_filter();
+                        return Ok(false);
+                    }
                     if key_event.code == KeyCode::Char('n')
                         && self.state == State::Home
                     {
                         self.state = State::NewConnection;
-                        self.new_connection_form = ConnectionInfoForm::new();
+                        self.connection_form = ConnectionInfoForm::new();
+                        return Ok(false);
+                    }
+                    if key_event.code == KeyCode::Char('e') {
+                        if let Some(connection) = self.connections_list.selected_connection(ctx) {
+                            self.state = State::EditConnection(connection.id);
+                            self.connection_form =
+                                ConnectionInfoForm::from_connection_info(&connection);
+                        }
+                        return Ok(false);
+                    }
+                    if key_event.code == KeyCode::Char('i') {
+                        tx.send(AppCommand::ScanImports).ok();
                         return Ok(false);
                     }
                     if key_event.code == KeyCode::Char('q') {
                         tx.send(AppCommand::Quit).ok();
                         return Ok(true);
                     }
-                    if key_event.code == KeyCode::Char('c') || key_event.code == KeyCode::Enter {
-                        tx.send(AppCommand::ConnectToDatabase(self.connections_list.selected())).ok();
+                    if key_event.code == KeyCode::Char('c') {
+                        if let Some(connection) = self.connections_list.selected_connection(ctx) {
+                            tx.send(AppCommand::ConnectToDatabase(connection.id)).ok();
+                        }
                         return Ok(false);
                     }
                     if key_event.code == KeyCode::Char('d') {
-                        tx.send(AppCommand::DeleteConnection(self.connections_list.selected())).ok();
+                        if let Some(connection) = self.connections_list.selected_connection(ctx) {
+                            self.state = State::ConfirmDelete(connection.id);
+                            self.delete_confirm = Confirm::new(
+                                "Delete Connection",
+                                format!("Delete connection \"{}\"?", connection.name),
+                            );
+                        }
                         return Ok(false);
                     }
                     self.connections_list.handle_event(event, ctx, tx)
                 }
                 _ => self.connections_list.handle_event(event, ctx, tx),
             },
-            State::NewConnection => {
+            State::NewConnection | State::EditConnection(_) => {
                 if event == Event::Key(KeyCode::Esc.into()) {
                     self.state = State::Home;
                     return Ok(false);
                 }
                 if let Event::Key(key_event) = event {
-                    if key_event.code == KeyCode::Enter && key_event.kind == KeyEventKind::Press {
-                        tx.send(AppCommand::SaveConnection(
-                            self.new_connection_form.to_connection_info(),
-                        ))
-                        .ok();
+                    if key_event.code == KeyCode::Enter
+                        && key_event.kind == KeyEventKind::Press
+                        && !self.connection_form.on_test_button()
+                    {
+                        let connection = match self.connection_form.to_connection_info() {
+                            Ok(connection) => connection,
+                            Err(e) => {
+                                self.connection_form.set_error(e.to_string());
+                                return Ok(false);
+                            }
+                        };
+                        let command = match self.state {
+                            State::EditConnection(id) => {
+                                AppCommand::UpdateConnection(id, connection)
+                            }
+                            _ => AppCommand::SaveConnection(connection),
+                        };
+                        tx.send(command).ok();
                         self.state = State::Home;
                         return Ok(false);
                     }
                 }
-                self.new_connection_form.handle_event(event, ctx, tx)
+                self.connection_form.handle_event(event, ctx, tx)
+            }
+            State::ConfirmDelete(id) => {
+                if let Event::Key(key_event) = event {
+                    match key_event.code {
+                        KeyCode::Char('y') => {
+                            tx.send(AppCommand::DeleteConnection(id)).ok();
+                            self.state = State::Home;
+                        }
+                        KeyCode::Char('n') | KeyCode::Esc => {
+                            self.state = State::Home;
+                        }
+                        KeyCode::Enter => {
+                            if self.delete_confirm.confirmed() {
+                                tx.send(AppCommand::DeleteConnection(id)).ok();
+                            }
+                            self.state = State::Home;
+                        }
+                        KeyCode::Left
+                        | KeyCode::Right
+                        | KeyCode::Tab
+                        | KeyCode::BackTab
+                        | KeyCode::Char('h')
+                        | KeyCode::Char('l') => {
+                            self.delete_confirm.toggle();
+                        }
+                        _ => {}
+                    }
+                }
+                Ok(false)
+            }
+            State::Import => {
+                if let Event::Key(key_event) = event {
+                    match key_event.code {
+                        KeyCode::Down | KeyCode::Char('j') => self.import_picker.next(),
+                        KeyCode::Up | KeyCode::Char('k') => self.import_picker.previous(),
+                        KeyCode::Char(' ') => self.import_picker.select(),
+                        KeyCode::Enter => {
+                            let selected = self
+                                .import_picker
+                                .selected()
+                                .unwrap_or_default()
+                                .into_iter()
+                                .cloned()
+                                .collect::<Vec<_>>();
+                            if !selected.is_empty() {
+                                tx.send(AppCommand::ImportConnections(selected)).ok();
+                            }
+                            self.state = State::Home;
+                        }
+                        KeyCode::Esc => self.state = State::Home,
+                        _ => {}
+                    }
+                }
+                Ok(false)
             }
         }
     }
@@ -113,13 +237,24 @@ impl AppWidget for &ConnectionScreen {
 
         match self.state {
             State::Home => {}
-            State::NewConnection => {
-                let popup = Popup::new("New Connection");
-                let form = &self.new_connection_form;
+            State::NewConnection | State::EditConnection(_) => {
+                let title = if self.state == State::NewConnection {
+                    "New Connection"
+                } else {
+                    "Edit Connection"
+                };
+                let popup = Popup::new(title);
+                let form = &self.connection_form;
                 popup.render_body(area, buf, |area, buf| {
                     form.render(area, buf);
                 });
             }
+            State::ConfirmDelete(_) => {
+                self.delete_confirm.render(area, buf);
+            }
+            State::Import => {
+                self.import_picker.render(area, buf);
+            }
         }
     }
 }
@@ -127,7 +262,8 @@ impl AppWidget for &ConnectionScreen {
 impl ConnectionScreen {
     fn render_instructions(&self, area: Rect, buf: &mut Buffer) {
         let paragraph = Paragraph::new(
-            "(n)ew connection, (e)dit connection, (d)elete connection, (c)onnect, (q)uit",
+            "(n)ew connection, (e)dit connection, (d)elete connection, (i)mport connections, \
+             (c)onnect, (/) filter, (space) fold group, (q)uit",
         )
         .wrap(Wrap { trim: true })
         .block(Block::default().borders(Borders::TOP));
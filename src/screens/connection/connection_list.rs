@@ -1,32 +1,223 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashSet};
+
 use color_eyre::eyre::Result;
 use crossterm::event::{Event, KeyCode};
-use ratatui::layout::Flex;
 use ratatui::prelude::*;
-use ratatui::widgets::Borders;
+use ratatui::widgets::{
+    BorderType, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
+};
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     widgets::{Block, Widget},
 };
 use tokio::sync::mpsc::UnboundedSender;
+use tui_textarea::TextArea;
 
 use crate::data::{AppCommand, Ctx};
 use crate::events::EventHandler;
+use crate::theme;
 use crate::widget::AppWidget;
 
 use super::ConnectionInfo;
 
+/// A line of the list, either a folder header or a connection
+#[derive(Debug)]
+enum Row {
+    Group {
+        name: String,
+        connections: usize,
+        folded: bool,
+    },
+    Connection(Box<ConnectionInfo>),
+}
+
+impl Row {
+    fn height(&self) -> u16 {
+        match self {
+            Row::Group { .. } => 1,
+            Row::Connection(_) => 3,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct ConnectionList {
+    /// Index into the rows that are shown
     selected: usize,
+    /// The first row that's shown, kept across renders so the list only
+    /// scrolls once the selection reaches an edge
+    offset: Cell<usize>,
+    /// Names of the groups whose connections are hidden
+    folded: HashSet<String>,
+    filter: TextArea<'static>,
+    /// Whether keys go to the filter box
+    filtering: bool,
 }
 
 impl ConnectionList {
     pub fn new() -> Self {
-        Self { selected: 0 }
+        Self {
+            selected: 0,
+            offset: Cell::new(0),
+            folded: HashSet::new(),
+            filter: filter_input(),
+            filtering: false,
+        }
+    }
+
+    pub fn is_filtering(&self) -> bool {
+        self.filtering
+    }
+
+    pub fn has_filter(&self) -> bool {
+        !self.filter_text().is_empty()
+    }
+
+    pub fn clear_filter(&mut self) {
+        self.filter = filter_input();
+        self.filtering = false;
+        self.selected = 0;
+    }
+
+    fn filter_text(&self) -> &str {
+        self.filter.lines()[0].trim()
+    }
+
+    /// The connections matching the filter, best matches first
+    fn visible_connections(&self, ctx: &Ctx) -> Vec<ConnectionInfo> {
+        let connections = ctx.read().unwrap().connections();
+        let pattern = self.filter_text();
+        if pattern.is_empty() {
+            return connections;
+        }
+        let mut matches = connections
+            .into_iter()
+            .filter_map(|c| match_score(pattern, &c).map(|score| (score, c)))
+            .collect::<Vec<_>>();
+        // Stable, so equally good matches keep the order they were saved in
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        matches.into_iter().map(|(_, c)| c).collect()
+    }
+
+    /// Connections without a group first, then a folder for each group in
+    /// alphabetical order. Matches for the filter are listed on their own,
+    /// best first.
+    fn rows(&self, ctx: &Ctx) -> Vec<Row> {
+        let connections = self.visible_connections(ctx);
+        if self.has_filter() {
+            return connections
+                .into_iter()
+                .map(|c| Row::Connection(Box::new(c)))
+                .collect();
+        }
+        let mut rows = vec![];
+        let mut groups: BTreeMap<String, Vec<ConnectionInfo>> = BTreeMap::new();
+        for connection in connections {
+            match connection.group.clone() {
+                Some(group) => groups.entry(group).or_default().push(connection),
+                None => rows.push(Row::Connection(Box::new(connection))),
+            }
+        }
+        for (name, connections) in groups {
+            let folded = self.folded.contains(&name);
+            rows.push(Row::Group {
+                name,
+                connections: connections.len(),
+                folded,
+            });
+            if !folded {
+                rows.extend(
+                    connections
+                        .into_iter()
+                        .map(|c| Row::Connection(Box::new(c))),
+                );
+            }
+        }
+        rows
     }
 
-    pub fn selected(&self) -> usize {
-        self.selected
+    /// The connection under the cursor, if it's on a connection and not a
+    /// group.
+    pub fn selected_connection(&self, ctx: &Ctx) -> Option<ConnectionInfo> {
+        let rows = self.rows(ctx);
+        let selected = self.selected.min(rows.len().saturating_sub(1));
+        match rows.into_iter().nth(selected) {
+            Some(Row::Connection(connection)) => Some(*connection),
+            _ => None,
+        }
+    }
+
+    /// Moves the cursor a row up or down, wrapping around at either end
+    fn move_selection(&mut self, ctx: &Ctx, up: bool) {
+        let last = self.rows(ctx).len().saturating_sub(1);
+        self.selected = match (up, self.selected.min(last)) {
+            (true, 0) => last,
+            (true, selected) => selected - 1,
+            (false, selected) if selected < last => selected + 1,
+            _ => 0,
+        };
+    }
+
+    /// Folds or unfolds the group under the cursor, or the group of the
+    /// connection under it. The cursor ends up on the group's header.
+    fn fold(&mut self, ctx: &Ctx, fold: Option<bool>) {
+        let rows = self.rows(ctx);
+        let selected = self.selected.min(rows.len().saturating_sub(1));
+        let group = match rows.get(selected) {
+            Some(Row::Group { name, .. }) => name.clone(),
+            // Only folding makes sense from inside the folder
+            Some(Row::Connection(connection)) if fold != Some(false) => match &connection.group {
+                Some(group) if !self.has_filter() => group.clone(),
+                _ => return,
+            },
+            _ => return,
+        };
+        let fold = fold.unwrap_or(!self.folded.contains(&group));
+        if fold {
+            self.folded.insert(group.clone());
+        } else {
+            self.folded.remove(&group);
+        }
+        let header = self
+            .rows(ctx)
+            .iter()
+            .position(|row| matches!(row, Row::Group { name, .. } if *name == group));
+        self.selected = header.unwrap_or_default();
+    }
+
+    /// Scrolls just enough for the selected row to be in view, and no further
+    /// than needed to fill the area
+    fn scroll(&self, rows: &[Row], selected: usize, height: u16) -> usize {
+        let height_of = |rows: &[Row]| rows.iter().map(Row::height).sum::<u16>();
+        let mut offset = self.offset.get().min(selected);
+        while offset < selected && height_of(&rows[offset..=selected]) > height {
+            offset += 1;
+        }
+        while offset > 0 && height_of(&rows[offset - 1..]) <= height {
+            offset -= 1;
+        }
+        self.offset.set(offset);
+        offset
+    }
+
+    fn render_group_header(
+        &self,
+        name: &str,
+        connections: usize,
+        folded: bool,
+        selected: bool,
+        area: Rect,
+        buf: &mut Buffer,
+    ) {
+        let marker = if folded { "\u{25b8}" } else { "\u{25be}" };
+        let line = Line::from(vec![
+            Span::raw(format!("{} ", marker)),
+            Span::raw(name).bold(),
+            Span::raw(format!(" ({})", connections)).dim(),
+        ]);
+        let line = if selected { line.light_magenta() } else { line };
+        line.render(area, buf);
     }
 
     // renders a block for a single connection showcasing all of its info
@@ -37,12 +228,25 @@ impl ConnectionList {
         area: Rect,
         buf: &mut Buffer,
     ) {
+        let mut title = vec![Span::raw(&*connection.name)];
+        if let Some(environment) = connection.environment {
+            let color = theme::environment_color(environment);
+            title.push(Span::raw(" "));
+            title.push(Span::styled(format!("[{}]", environment.label()), color));
+        }
         let block = Block::default()
-            .title(&*connection.name)
+            .title(Line::from(title))
             .borders(Borders::ALL);
 
+        // The environment tints the border, so the selection thickens it too
+        let border_style = match connection.environment {
+            Some(environment) => Style::default().fg(theme::environment_color(environment)),
+            None if selected => Style::default().fg(Color::LightMagenta),
+            None => Style::default(),
+        };
+        let block = block.border_style(border_style);
         let block = if selected {
-            block.border_style(Style::default().fg(Color::LightMagenta))
+            block.border_type(BorderType::Thick)
         } else {
             block
         };
@@ -74,57 +278,269 @@ impl EventHandler for ConnectionList {
         event: Event,
         ctx: &Ctx,
         tx: &UnboundedSender<AppCommand>,
+    ) -> Result<bool> {
+        if self.filtering {
+            return self.handle_filter_event(event, ctx, tx);
+        }
+        match event {
+            Event::Key(key_event) => match key_event.code {
+                KeyCode::Char('/') => self.filtering = true,
+                KeyCode::Up | KeyCode::Char('k') => self.move_selection(ctx, true),
+                KeyCode::Down | KeyCode::Char('j') => self.move_selection(ctx, false),
+                KeyCode::Left | KeyCode::Char('h') => self.fold(ctx, Some(true)),
+                KeyCode::Right | KeyCode::Char('l') => self.fold(ctx, Some(false)),
+                KeyCode::Char(' ') => self.fold(ctx, None),
+                KeyCode::Enter => match self.selected_connection(ctx) {
+                    Some(connection) => {
+                        tx.send(AppCommand::ConnectToDatabase(connection.id)).ok();
+                    }
+                    None => self.fold(ctx, None),
+                },
+                _ => {}
+            },
+            _ => {}
+        }
+        Ok(false)
+    }
+}
+
+impl ConnectionList {
+    /// Keys typed into the filter box. The arrows still move through the
+    /// matches, enter connects to the selected one and esc clears the filter.
+    fn handle_filter_event(
+        &mut self,
+        event: Event,
+        ctx: &Ctx,
+        tx: &UnboundedSender<AppCommand>,
     ) -> Result<bool> {
         match event {
             Event::Key(key_event) => match key_event.code {
-                KeyCode::Up | KeyCode::Char('k') => {
-                    if self.selected > 0 {
-                        self.selected -= 1;
-                    } else {
-                        self.selected = ctx.read().unwrap().connections.len().saturating_sub(1);
+                KeyCode::Esc => self.clear_filter(),
+                KeyCode::Up => self.move_selection(ctx, true),
+                KeyCode::Down => self.move_selection(ctx, false),
+                KeyCode::Enter => {
+                    // The list stays narrowed to the matches, esc clears it
+                    self.filtering = false;
+                    if let Some(connection) = self.selected_connection(ctx) {
+                        tx.send(AppCommand::ConnectToDatabase(connection.id)).ok();
                     }
                 }
-                KeyCode::Down | KeyCode::Char('j') => {
-                    if self.selected < ctx.read().unwrap().connections.len().saturating_sub(1) {
-                        self.selected += 1;
-                    } else {
+                _ => {
+                    if self.filter.input(event) {
                         self.selected = 0;
                     }
                 }
-                KeyCode::Enter => {
-                    tx.send(AppCommand::ConnectToDatabase(self.selected)).ok();
-                }
-                _ => {}
             },
+            Event::Paste(text) => {
+                self.filter.insert_str(text.lines().collect::<String>());
+                self.selected = 0;
+            }
             _ => {}
         }
         Ok(false)
     }
 }
 
+fn filter_input() -> TextArea<'static> {
+    let mut filter = TextArea::default();
+    filter.set_cursor_line_style(Style::default());
+    filter.set_block(
+        Block::default()
+            .title("Filter (enter to connect, esc to clear)")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::LightMagenta)),
+    );
+    filter
+}
+
+/// How well the pattern matches the connection's name, host, database,
+/// environment tag or group, or `None` if it matches none of them
+fn match_score(pattern: &str, connection: &ConnectionInfo) -> Option<i64> {
+    let environment = connection
+        .environment
+        .map(|e| e.label())
+        .unwrap_or_default();
+    [
+        connection.name.as_str(),
+        &connection.host,
+        &connection.database,
+        environment,
+        connection.group.as_deref().unwrap_or_default(),
+    ]
+    .into_iter()
+    .filter_map(|text| fuzzy_score(pattern, text))
+    .max()
+}
+
+/// Matches the pattern's characters in order anywhere in the text, ignoring
+/// case. Runs of consecutive characters and matches at the start of a word
+/// score higher, gaps between matches score lower.
+fn fuzzy_score(pattern: &str, text: &str) -> Option<i64> {
+    let text = text.chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous: Option<usize> = None;
+    for p in pattern.chars().flat_map(char::to_lowercase) {
+        let found =
+            (position..text.len()).find(|&i| text[i].to_lowercase().eq(std::iter::once(p)))?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 5;
+        } else if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        if let Some(previous) = previous {
+            score -= (found - previous - 1).min(5) as i64;
+        }
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
 impl AppWidget for ConnectionList {
     fn render(&self, area: Rect, buf: &mut Buffer, ctx: &Ctx)
     where
         Self: Sized,
     {
-        let block = Block::default().title("Connections").borders(Borders::ALL);
-        let block_area = block.inner(area);
+        let total = ctx.read().unwrap().connections.len();
+        let rows = self.rows(ctx);
+        let title = if self.has_filter() {
+            format!("Connections ({} of {})", rows.len(), total)
+        } else {
+            "Connections".to_string()
+        };
+        let block = Block::default().title(title).borders(Borders::ALL);
+        let mut block_area = block.inner(area);
         block.render(area, buf);
 
-        let conns = ctx.read().unwrap().connections();
-        let layout = Layout::default()
-            .direction(Direction::Vertical)
-            .flex(Flex::Start)
-            .constraints::<Vec<Constraint>>(
-                conns
-                    .iter()
-                    .map(|_| Constraint::Length(3))
-                    .collect::<Vec<_>>(),
-            )
-            .split(block_area);
+        if self.filtering || self.has_filter() {
+            let [filter_area, rest] =
+                Layout::vertical([Constraint::Length(3), Constraint::Fill(1)]).areas(block_area);
+            self.filter.widget().render(filter_area, buf);
+            block_area = rest;
+            if rows.is_empty() {
+                Paragraph::new("No connections match").render(block_area, buf);
+            }
+        }
+
+        // The selected connection may have just been deleted
+        let selected = self.selected.min(rows.len().saturating_sub(1));
+        let offset = self.scroll(&rows, selected, block_area.height);
+        let mut y = block_area.y;
+        for (i, row) in rows.iter().enumerate().skip(offset) {
+            if y + row.height() > block_area.bottom() {
+                break;
+            }
+            let row_area = Rect::new(block_area.x, y, block_area.width, row.height());
+            y += row.height();
+            match row {
+                Row::Group {
+                    name,
+                    connections,
+                    folded,
+                } => self.render_group_header(
+                    name,
+                    *connections,
+                    *folded,
+                    selected == i,
+                    row_area,
+                    buf,
+                ),
+                Row::Connection(connection) => {
+                    // Connections in a folder are indented under its header
+                    let row_area = match connection.group {
+                        Some(_) if !self.has_filter() => row_area.inner(&Margin::new(1, 0)),
+                        _ => row_area,
+                    };
+                    self.render_connection_block(connection, selected == i, row_area, buf);
+                }
+            }
+        }
+
+        let content_height = rows.iter().map(Row::height).sum::<u16>();
+        if content_height > block_area.height {
+            let mut state = ScrollbarState::new(rows.len()).position(selected);
+            let scrollbar_area = Rect {
+                y: block_area.y,
+                height: block_area.height,
+                ..area
+            };
+            Scrollbar::new(ScrollbarOrientation::VerticalRight).render(
+                scrollbar_area,
+                buf,
+                &mut state,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::{DatabaseType, Environment};
 
-        for (i, connection) in conns.iter().enumerate() {
-            self.render_connection_block(connection, self.selected == i, layout[i], buf);
+    use super::*;
+
+    fn connection(name: &str) -> ConnectionInfo {
+        ConnectionInfo {
+            id: 0,
+            name: name.to_string(),
+            host: "localhost".to_string(),
+            port: 5432,
+            user: String::new(),
+            password: String::new(),
+            database: "app".to_string(),
+            data_files: vec![],
+            database_type: DatabaseType::Postgres,
+            options: vec![],
+            tls: Default::default(),
+            ssh: None,
+            read_only: false,
+            environment: None,
+            group: None,
+            advanced: Default::default(),
         }
     }
+
+    #[test]
+    fn prefixes_beat_gaps() {
+        assert!(fuzzy_score("ord", "orders") > fuzzy_score("ord", "old records"));
+        assert!(fuzzy_score("ord", "billing-orders") > fuzzy_score("ord", "borders"));
+    }
+
+    #[test]
+    fn runs_beat_scattered_letters() {
+        assert!(fuzzy_score("prod", "my-product") > fuzzy_score("prod", "p-r-o-d"));
+        assert!(fuzzy_score("api", "rapid") > fuzzy_score("api", "a pair"));
+    }
+
+    #[test]
+    fn matching_ignores_case() {
+        assert_eq!(fuzzy_score("ORD", "orders"), fuzzy_score("ord", "orders"));
+        assert_eq!(fuzzy_score("ord", "ORDERS"), fuzzy_score("ord", "orders"));
+        assert_eq!(fuzzy_score("ordx", "orders"), None);
+        assert_eq!(fuzzy_score("dro", "orders"), None);
+    }
+
+    #[test]
+    fn connections_match_on_group_and_environment() {
+        let mut connection = connection("billing");
+        assert_eq!(match_score("acme", &connection), None);
+        assert_eq!(match_score("prod", &connection), None);
+        connection.group = Some("Acme".to_string());
+        connection.environment = Some(Environment::Prod);
+        assert_eq!(
+            match_score("acme", &connection),
+            fuzzy_score("acme", "Acme")
+        );
+        assert_eq!(
+            match_score("prod", &connection),
+            fuzzy_score("prod", "prod")
+        );
+        // The best of the fields counts
+        assert_eq!(
+            match_score("bill", &connection),
+            fuzzy_score("bill", "billing")
+        );
+    }
 }
@@ -1,42 +1,157 @@
 use crate::data::AppCommand;
 use crate::data::Ctx;
+use crate::data::DatabaseType;
+use crate::data::Environment;
 use crate::events::EventHandler;
+use crate::results::{Cell, ResultSet, Summary};
+use crate::sql;
+use crate::theme;
 use crate::widget::AppWidget;
 use color_eyre::eyre::Result;
 use crossterm::event::Event;
 use crossterm::event::KeyCode;
 use crossterm::event::KeyEvent;
 use crossterm::event::KeyModifiers;
+use dbterm_widgets::picker::Picker;
+use dbterm_widgets::popup::Popup;
 use ratatui::prelude::*;
 use ratatui::widgets::*;
 use tokio::sync::mpsc::UnboundedSender;
-use tui_textarea::TextArea;
+use tui_textarea::{CursorMove, Scrolling, TextArea};
+
+const QUERY_TITLE: &str = "Query (ctrl+p switch connection, ctrl+n open another)";
+const RESULTS_TITLE: &str = "Results (pgup/pgdn to scroll)";
+const SCRIPT_RESULTS_TITLE: &str = "Results (pgup/pgdn to scroll, alt+←/→ switch statement)";
+const SWITCHER_TITLE: &str = "Switch Connection";
+const CONFIRM_TITLE: &str = "Destructive Statement";
+/// How close to the last fetched row scrolling gets before the next page is
+/// asked for
+const FETCH_MARGIN: usize = 100;
+/// Notices shown under the results, the most recent ones if there are more
+const MAX_NOTICES: usize = 5;
 
 enum State {
     Query,
+    SwitchConnection,
+    /// Waiting for the connection name to be typed before running a query
+    /// that could destroy data on prod
+    ConfirmDestructive(String),
 }
 
-pub struct MainScreen {
+/// A connection opened this session
+struct OpenConnection {
+    id: usize,
+    name: String,
+    database_type: DatabaseType,
+    environment: Option<Environment>,
+}
+
+/// What one statement of the query came back with
+struct StatementResult {
+    /// The statement as it was run
+    sql: String,
     output: TextArea<'static>,
+    /// Rows in the output, not counting the header
+    rows: usize,
+    /// Whether the statement has more rows than it sent so far
+    more_rows: bool,
+    /// Shown under the results
+    summary: Option<Summary>,
+    /// Why the statement failed, shown in place of the results
+    error: Option<String>,
+}
+
+impl StatementResult {
+    fn new(sql: String, title: &'static str, border_style: Style) -> Self {
+        Self {
+            sql,
+            output: output_area(vec![], title, border_style),
+            rows: 0,
+            more_rows: false,
+            summary: None,
+            error: None,
+        }
+    }
+
+    /// Adds the next page of rows below the ones showing, leaving the view
+    /// where it is
+    fn append_output(&mut self, rows: &[Vec<Cell>], more: bool) {
+        if !rows.is_empty() {
+            let (row, col) = self.output.cursor();
+            self.output.move_cursor(CursorMove::Bottom);
+            self.output.move_cursor(CursorMove::End);
+            let lines = rows.iter().map(|row| row_line(row)).collect::<Vec<_>>();
+            self.output.insert_str(format!("\n{}", lines.join("\n")));
+            self.output
+                .move_cursor(CursorMove::Jump(row as u16, col as u16));
+        }
+        self.rows += rows.len();
+        self.more_rows = more;
+    }
+
+    /// How long the statement took, and how many rows it returned or affected
+    fn summary_line(&self) -> Option<String> {
+        let summary = self.summary.as_ref()?;
+        let timing = summary.timing();
+        let line = match summary.rows_affected {
+            Some(affected) => format!("{} rows affected in {}", affected, timing),
+            None if self.more_rows => format!(
+                "{} rows so far in {}, scroll down for more",
+                self.rows, timing
+            ),
+            None => format!("{} rows in {}", self.rows, timing),
+        };
+        Some(line)
+    }
+
+    /// Numbered from 1 and named after the statement's first keyword, like
+    /// `2 insert`
+    fn tab_title(&self, index: usize, database_type: DatabaseType) -> Line<'static> {
+        let keyword = sql::tokens(&self.sql, database_type)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        let title = Line::from(format!("{} {}", index + 1, keyword));
+        if self.error.is_some() {
+            title.red()
+        } else {
+            title
+        }
+    }
+}
+
+pub struct MainScreen {
+    /// One per statement of the last query, of which one is shown at a time
+    statements: Vec<StatementResult>,
+    shown: usize,
+    /// Whether the next page was asked for and hasn't come in yet
+    fetching: bool,
+    /// Whether a statement was sent and hasn't been answered yet
+    running: bool,
+    /// Whether scripts keep going past a failing statement, toggled with
+    /// ctrl+o
+    continue_on_error: bool,
     input: TextArea<'static>,
     state: State,
+    /// The open connections, in the order they were opened
+    connections: Vec<OpenConnection>,
+    active: Option<usize>,
+    switcher: Picker<'static, 'static, usize>,
+    confirm_input: TextArea<'static>,
+    /// Whether the name typed into the confirmation didn't match
+    confirm_mismatch: bool,
 }
 
 impl MainScreen {
     pub fn new() -> Self {
-        let body = Block::default()
-            .title("Results")
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::LightMagenta));
-
-        let mut output = TextArea::default();
-        output.set_line_number_style(Style::default());
-        output.set_cursor_line_style(Style::default());
-        output.set_cursor_style(Style::default());
-        output.set_block(body);
+        let output = StatementResult::new(
+            String::new(),
+            RESULTS_TITLE,
+            Style::default().fg(Color::LightMagenta),
+        );
 
         let footer = Block::default()
-            .title("Query")
+            .title(QUERY_TITLE)
             .borders(Borders::ALL)
             .border_style(Style::default().fg(Color::LightMagenta));
 
@@ -45,31 +160,311 @@ impl MainScreen {
         input.set_block(footer);
 
         Self {
-            output,
+            statements: vec![output],
+            shown: 0,
+            fetching: false,
+            running: false,
+            continue_on_error: false,
             input,
             state: State::Query,
+            connections: vec![],
+            active: None,
+            switcher: Picker::single(SWITCHER_TITLE, Vec::<(String, _)>::new()),
+            confirm_input: TextArea::default(),
+            confirm_mismatch: false,
+        }
+    }
+
+    pub fn set_active_connection(
+        &mut self,
+        id: usize,
+        name: String,
+        database_type: DatabaseType,
+        environment: Option<Environment>,
+    ) {
+        let connection = OpenConnection {
+            id,
+            name,
+            database_type,
+            environment,
+        };
+        // Connections that were edited come back with their new name and
+        // environment
+        match self.connections.iter_mut().find(|c| c.id == id) {
+            Some(existing) => *existing = connection,
+            None => self.connections.push(connection),
+        }
+        self.active = Some(id);
+        let border_style = self.border_style();
+        let outputs = self.statements.iter_mut().map(|s| &mut s.output);
+        for input in outputs.chain([&mut self.input]) {
+            let block = input.block().cloned().unwrap_or_default();
+            input.set_block(block.border_style(border_style));
+        }
+    }
+
+    /// Forgets a connection that was closed. Returns whether it was the
+    /// active one.
+    pub fn close_connection(&mut self, id: usize) -> bool {
+        self.connections.retain(|c| c.id != id);
+        if self.active != Some(id) {
+            return false;
+        }
+        self.active = None;
+        true
+    }
+
+    fn active_connection(&self) -> Option<&OpenConnection> {
+        self.connections.iter().find(|c| Some(c.id) == self.active)
+    }
+
+    /// What the active connection's SQL is lexed as
+    fn database_type(&self) -> DatabaseType {
+        self.active_connection()
+            .map_or(DatabaseType::Postgres, |c| c.database_type)
+    }
+
+    /// Tinted with the active connection's environment
+    fn border_style(&self) -> Style {
+        let color = self
+            .active_connection()
+            .and_then(|c| c.environment)
+            .map(theme::environment_color)
+            .unwrap_or(Color::LightMagenta);
+        Style::default().fg(color)
+    }
+
+    /// Sends the query, unless it could destroy data on a prod connection, in
+    /// which case the connection name has to be typed in first
+    fn run_query(&mut self, query: String, tx: &UnboundedSender<AppCommand>) {
+        let prod = self
+            .active_connection()
+            .is_some_and(|c| c.environment == Some(Environment::Prod));
+        if prod && sql::is_destructive(&query, self.database_type()) {
+            let mut confirm_input = TextArea::default();
+            confirm_input.set_cursor_line_style(Style::default());
+            confirm_input.set_block(
+                Block::default()
+                    .title("Connection name")
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(Color::Red)),
+            );
+            self.confirm_input = confirm_input;
+            self.confirm_mismatch = false;
+            self.state = State::ConfirmDestructive(query);
+            return;
         }
+        tx.send(AppCommand::Query(query)).ok();
+    }
+
+    fn open_switcher(&mut self) {
+        let items = self
+            .connections
+            .iter()
+            .map(|c| {
+                let label = if self.active == Some(c.id) {
+                    format!("{} (active)", c.name)
+                } else {
+                    c.name.clone()
+                };
+                (label, c.id)
+            })
+            .collect();
+        self.switcher = Picker::single(SWITCHER_TITLE, items);
+        self.state = State::SwitchConnection;
     }
 
-    pub fn set_output(&mut self, headers: Vec<String>, rows: Vec<Vec<String>>) {
-        let body = Block::default()
-            .title("Results")
+    pub fn set_query(&mut self, query: &str) {
+        let footer = Block::default()
+            .title(QUERY_TITLE)
             .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::LightMagenta));
+            .border_style(self.border_style());
+
+        let mut input = TextArea::from(query.lines());
+        input.set_cursor_line_style(Style::default());
+        input.set_block(footer);
+        self.input = input;
+    }
+
+    fn results_title(&self) -> &'static str {
+        if self.statements.len() > 1 {
+            SCRIPT_RESULTS_TITLE
+        } else {
+            RESULTS_TITLE
+        }
+    }
 
-        let rows = rows
+    /// Makes room for the results of each of the query's statements
+    pub fn start_query(&mut self, statements: Vec<String>) {
+        let title = if statements.len() > 1 {
+            SCRIPT_RESULTS_TITLE
+        } else {
+            RESULTS_TITLE
+        };
+        let border_style = self.border_style();
+        self.statements = statements
+            .into_iter()
+            .map(|sql| StatementResult::new(sql, title, border_style))
+            .collect();
+        self.shown = 0;
+        self.fetching = false;
+        self.running = true;
+    }
+
+    /// Shows the first page of a statement's rows. Statements that come
+    /// after it are still waiting to be answered.
+    pub fn set_output(
+        &mut self,
+        statement: usize,
+        result: &ResultSet,
+        more: bool,
+        summary: Summary,
+    ) {
+        let title = self.results_title();
+        let border_style = self.border_style();
+        let Some(entry) = self.statements.get_mut(statement) else {
+            return;
+        };
+        let headers = result
+            .columns
             .iter()
-            .map(|row| row.join("\t"))
-            .collect::<Vec<String>>();
+            .map(|column| column.name.as_str())
+            .collect::<Vec<_>>()
+            .join("\t");
+        let rows = result.rows.iter().map(|row| row_line(row));
+        let lines = vec![headers].into_iter().chain(rows).collect::<Vec<_>>();
+        entry.output = output_area(lines, title, border_style);
+        entry.rows = result.rows.len();
+        entry.more_rows = more;
+        entry.summary = Some(summary);
+        self.shown = statement;
+        self.fetching = false;
+        self.running = statement + 1 < self.statements.len();
+    }
+
+    pub fn append_output(&mut self, statement: usize, rows: &[Vec<Cell>], more: bool) {
+        if let Some(entry) = self.statements.get_mut(statement) {
+            entry.append_output(rows, more);
+        }
+        if statement + 1 == self.statements.len() {
+            self.fetching = false;
+        }
+    }
+
+    pub fn add_notices(&mut self, statement: usize, notices: Vec<String>) {
+        if let Some(summary) = self
+            .statements
+            .get_mut(statement)
+            .and_then(|entry| entry.summary.as_mut())
+        {
+            summary.notices.extend(notices);
+        }
+    }
+
+    /// Shows the error in place of the statement's results
+    pub fn set_statement_failed(&mut self, statement: usize, error: String) {
+        let title = self.results_title();
+        let border_style = self.border_style();
+        let Some(entry) = self.statements.get_mut(statement) else {
+            return;
+        };
+        let mut output = output_area(
+            error.lines().map(String::from).collect(),
+            title,
+            border_style,
+        );
+        output.set_style(Style::default().fg(Color::Red));
+        entry.output = output;
+        entry.error = Some(error);
+        self.shown = statement;
+        self.running = statement + 1 < self.statements.len();
+    }
+
+    fn shown_statement(&self) -> &StatementResult {
+        &self.statements[self.shown]
+    }
+
+    pub fn summary_line(&self) -> Option<String> {
+        self.shown_statement().summary_line()
+    }
+
+    pub fn notice_count(&self) -> usize {
+        let summary = self.shown_statement().summary.as_ref();
+        summary.map_or(0, |s| s.notices.len())
+    }
+
+    pub fn has_more_rows(&self) -> bool {
+        self.shown_statement().more_rows
+    }
 
-        let rows = vec![headers.join("\t")].into_iter().chain(rows).collect::<Vec<_>>();
-        let mut output = TextArea::from(rows);
-        output.set_line_number_style(Style::default());
-        output.set_block(body);
-        output.set_cursor_line_style(Style::default());
-        output.set_cursor_style(Style::default());
-        self.output = output;
+    /// Which statement is shown, like `Statement 2 of 4`, for scripts
+    pub fn statement_label(&self) -> Option<String> {
+        (self.statements.len() > 1)
+            .then(|| format!("Statement {} of {}", self.shown + 1, self.statements.len()))
     }
+
+    /// Whether `statement` is the one whose results are showing
+    pub fn is_showing(&self, statement: usize) -> bool {
+        self.shown == statement
+    }
+
+    /// The query's task is over, nothing more is coming for it
+    pub fn finish_query(&mut self) {
+        self.running = false;
+        self.fetching = false;
+    }
+
+    /// Whether there's a query, or a page of its rows, to cancel waiting on
+    pub fn is_query_running(&self) -> bool {
+        self.running || self.fetching
+    }
+
+    /// Scrolls the results, asking for the next page once the view gets
+    /// close to the last row fetched so far. Only the last statement's rows
+    /// wait to be asked for.
+    fn scroll_output(&mut self, scrolling: Scrolling, tx: &UnboundedSender<AppCommand>) {
+        let last = self.shown + 1 == self.statements.len();
+        let entry = &mut self.statements[self.shown];
+        entry.output.scroll(scrolling);
+        let (row, _) = entry.output.cursor();
+        let near_end = row + FETCH_MARGIN >= entry.output.lines().len();
+        if near_end && last && entry.more_rows && !self.fetching {
+            self.fetching = true;
+            tx.send(AppCommand::FetchRows).ok();
+        }
+    }
+
+    fn show_statement(&mut self, statement: usize) {
+        if statement < self.statements.len() {
+            self.shown = statement;
+        }
+    }
+}
+
+fn output_area(lines: Vec<String>, title: &'static str, border_style: Style) -> TextArea<'static> {
+    let body = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(border_style);
+    let mut output = if lines.is_empty() {
+        TextArea::default()
+    } else {
+        TextArea::from(lines)
+    };
+    output.set_line_number_style(Style::default());
+    output.set_block(body);
+    output.set_cursor_line_style(Style::default());
+    output.set_cursor_style(Style::default());
+    // Rows are only ever appended, there's nothing to undo
+    output.set_max_histories(0);
+    output
+}
+
+fn row_line(row: &[Cell]) -> String {
+    row.iter()
+        .map(|cell| cell.to_string())
+        .collect::<Vec<_>>()
+        .join("\t")
 }
 
 impl EventHandler for MainScreen {
@@ -79,26 +474,123 @@ impl EventHandler for MainScreen {
         _ctx: &Ctx,
         tx: &UnboundedSender<AppCommand>,
     ) -> Result<bool> {
-
         match self.state {
             State::Query => match event {
                 Event::Key(key_event) => match key_event {
+                    KeyEvent {
+                        code: KeyCode::Char('p'),
+                        modifiers: KeyModifiers::CONTROL,
+                        ..
+                    } => {
+                        self.open_switcher();
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char('r'),
+                        modifiers: KeyModifiers::CONTROL,
+                        ..
+                    } => {
+                        tx.send(AppCommand::RetryQuery).ok();
+                    }
+                    KeyEvent {
+                        code: KeyCode::Esc, ..
+                    } if self.is_query_running() => {
+                        tx.send(AppCommand::CancelQuery).ok();
+                    }
+                    KeyEvent {
+                        code: KeyCode::PageDown,
+                        ..
+                    } => {
+                        self.scroll_output(Scrolling::PageDown, tx);
+                    }
+                    KeyEvent {
+                        code: KeyCode::PageUp,
+                        ..
+                    } => {
+                        self.scroll_output(Scrolling::PageUp, tx);
+                    }
+                    KeyEvent {
+                        code: KeyCode::Left,
+                        modifiers: KeyModifiers::ALT,
+                        ..
+                    } => {
+                        self.show_statement(self.shown.saturating_sub(1));
+                    }
+                    KeyEvent {
+                        code: KeyCode::Right,
+                        modifiers: KeyModifiers::ALT,
+                        ..
+                    } => {
+                        self.show_statement(self.shown + 1);
+                    }
+                    KeyEvent {
+                        code: KeyCode::Char('o'),
+                        modifiers: KeyModifiers::CONTROL,
+                        ..
+                    } => {
+                        self.continue_on_error = !self.continue_on_error;
+                        tx.send(AppCommand::ContinueOnError(self.continue_on_error))
+                            .ok();
+                    }
                     KeyEvent {
                         code: KeyCode::Enter,
                         // switch to cmd/ctrl + enter after this is resolved https://github.com/crossterm-rs/crossterm/issues/515
                         ..
                     } => {
-                        tx.send(AppCommand::Query(self.input.lines().join("\n")))
-                            .ok();
+                        self.run_query(self.input.lines().join("\n"), tx);
                     }
                     _ => {
                         self.input.input(event);
                     }
                 },
+                Event::Paste(text) => {
+                    self.input.insert_str(text);
+                }
                 _ => {
                     self.input.input(event);
                 }
             },
+            State::SwitchConnection => {
+                if let Event::Key(key_event) = event {
+                    match key_event.code {
+                        KeyCode::Down | KeyCode::Char('j') => self.switcher.next(),
+                        KeyCode::Up | KeyCode::Char('k') => self.switcher.previous(),
+                        KeyCode::Enter => {
+                            // Enter picks the highlighted connection, no need
+                            // to select it with space first
+                            self.switcher.select();
+                            let selected = self.switcher.selected().unwrap_or_default();
+                            if let Some(&&id) = selected.first() {
+                                tx.send(AppCommand::SwitchConnection(id)).ok();
+                            }
+                            self.state = State::Query;
+                        }
+                        KeyCode::Esc => self.state = State::Query,
+                        _ => {}
+                    }
+                }
+            }
+            State::ConfirmDestructive(ref query) => match event {
+                Event::Key(key_event) if key_event.code == KeyCode::Enter => {
+                    let name = self.active_connection().map(|c| c.name.as_str());
+                    if name == Some(self.confirm_input.lines()[0].trim()) {
+                        tx.send(AppCommand::Query(query.clone())).ok();
+                        self.state = State::Query;
+                    } else {
+                        self.confirm_mismatch = true;
+                    }
+                }
+                Event::Key(key_event) if key_event.code == KeyCode::Esc => {
+                    self.state = State::Query;
+                }
+                Event::Paste(text) => {
+                    self.confirm_input
+                        .insert_str(text.lines().collect::<String>());
+                }
+                _ => {
+                    self.confirm_mismatch = false;
+                    self.confirm_input.input(event);
+                }
+            },
         };
         Ok(false)
     }
@@ -106,12 +598,15 @@ impl EventHandler for MainScreen {
 
 impl AppWidget for MainScreen {
     fn render(&self, area: Rect, buf: &mut Buffer, ctx: &Ctx) {
+        let footer = self.footer();
         let layout = Layout::default()
             .direction(Direction::Vertical)
             .constraints(
                 [
                     // Constraint::Length(3),
+                    Constraint::Length(self.tabs_height()),
                     Constraint::Min(1),
+                    Constraint::Length(footer.len() as u16),
                     Constraint::Length(3),
                 ]
                 .as_ref(),
@@ -123,7 +618,114 @@ impl AppWidget for MainScreen {
         //     .borders(Borders::ALL)
         //     .border_style(Style::default().fg(Color::LightMagenta));
         // header.render(layout[0], buf);
-        self.output.widget().render(layout[0], buf);
-        self.input.widget().render(layout[1], buf);
+        self.render_tabs(layout[0], buf);
+        self.shown_statement()
+            .output
+            .widget()
+            .render(layout[1], buf);
+        Paragraph::new(footer).render(layout[2], buf);
+        self.input.widget().render(layout[3], buf);
+
+        match self.state {
+            State::Query => {}
+            State::SwitchConnection => self.switcher.render(area, buf),
+            State::ConfirmDestructive(_) => self.render_confirm(area, buf),
+        }
+    }
+}
+
+impl MainScreen {
+    /// Scripts list their statements above the results
+    fn tabs_height(&self) -> u16 {
+        if self.statements.len() > 1 {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn render_tabs(&self, area: Rect, buf: &mut Buffer) {
+        if self.statements.len() < 2 {
+            return;
+        }
+        let database_type = self.database_type();
+        let titles = self
+            .statements
+            .iter()
+            .enumerate()
+            .map(|(index, statement)| statement.tab_title(index, database_type))
+            .collect::<Vec<_>>();
+        Tabs::new(titles)
+            .select(self.shown)
+            .highlight_style(Style::default().fg(Color::LightMagenta).bold())
+            .render(area, buf);
+    }
+
+    /// Under the results: the summary line and the latest notices, or what
+    /// became of a statement that has no results
+    fn footer(&self) -> Vec<Line<'_>> {
+        let statement = self.shown_statement();
+        if statement.error.is_some() {
+            return vec![Line::from("Failed").red()];
+        }
+        let (Some(summary), Some(line)) = (&statement.summary, statement.summary_line()) else {
+            if self.statements.len() < 2 {
+                return vec![];
+            }
+            // Statements are answered in order, so the first one without an
+            // answer is the one running
+            let current = self
+                .statements
+                .iter()
+                .position(|s| s.summary.is_none() && s.error.is_none());
+            let line = match self.running {
+                true if current == Some(self.shown) => "Running",
+                true => "Waiting to run",
+                false => "Didn't run",
+            };
+            return vec![Line::from(line).dim()];
+        };
+        let skipped = summary.notices.len().saturating_sub(MAX_NOTICES);
+        std::iter::once(Line::from(line).dim())
+            .chain(
+                summary.notices[skipped..]
+                    .iter()
+                    .map(|notice| Line::from(notice.as_str()).yellow()),
+            )
+            .collect()
+    }
+
+    fn render_confirm(&self, area: Rect, buf: &mut Buffer) {
+        let name = self
+            .active_connection()
+            .map(|c| c.name.as_str())
+            .unwrap_or_default();
+        Popup::new(CONFIRM_TITLE)
+            .size(60, 40)
+            .render_body(area, buf, |area, buf| {
+                let layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .margin(1)
+                    .constraints([
+                        Constraint::Fill(1),
+                        Constraint::Length(3),
+                        Constraint::Length(1),
+                    ])
+                    .split(area);
+                Paragraph::new(format!(
+                    "This statement can destroy data on the prod connection \"{}\". \
+                     Type the connection name to run it anyway.",
+                    name
+                ))
+                .wrap(Wrap { trim: true })
+                .render(layout[0], buf);
+                self.confirm_input.widget().render(layout[1], buf);
+                let hint = if self.confirm_mismatch {
+                    Paragraph::new("That's not the connection name").red()
+                } else {
+                    Paragraph::new("enter to run, esc to cancel")
+                };
+                hint.render(layout[2], buf);
+            });
     }
 }
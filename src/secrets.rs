@@ -0,0 +1,174 @@
+use std::{collections::BTreeMap, fs::OpenOptions, io::Write, path::PathBuf};
+
+use color_eyre::eyre::Result;
+
+const SERVICE: &str = "dbterm";
+
+/// Set to `file` to keep secrets in a JSON file instead of the OS keyring, for
+/// machines without a keyring daemon (CI, containers, ssh sessions).
+pub const SECRET_STORE_ENV: &str = "DBTERM_SECRET_STORE";
+
+/// Somewhere to keep connection passwords, keyed by connection id.
+pub trait SecretStore {
+    fn get(&self, id: usize) -> Result<Option<String>>;
+    fn set(&self, id: usize, secret: &str) -> Result<()>;
+    fn delete(&self, id: usize) -> Result<()>;
+}
+
+/// Picks the backend based on [`SECRET_STORE_ENV`], defaulting to the OS keyring.
+pub fn store() -> Box<dyn SecretStore> {
+    match std::env::var(SECRET_STORE_ENV).as_deref() {
+        Ok("file") => Box::new(FileStore::new(secrets_path())),
+        _ => Box::new(KeyringStore),
+    }
+}
+
+fn account(id: usize) -> String {
+    format!("connection-{}", id)
+}
+
+pub struct KeyringStore;
+
+impl SecretStore for KeyringStore {
+    fn get(&self, id: usize) -> Result<Option<String>> {
+        match keyring::Entry::new(SERVICE, &account(id))?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn set(&self, id: usize, secret: &str) -> Result<()> {
+        if secret.is_empty() {
+            return self.delete(id);
+        }
+        keyring::Entry::new(SERVICE, &account(id))?.set_password(secret)?;
+        Ok(())
+    }
+
+    fn delete(&self, id: usize) -> Result<()> {
+        match keyring::Entry::new(SERVICE, &account(id))?.delete_password() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Plaintext fallback, readable only by the current user on unix.
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn read(&self) -> Result<BTreeMap<String, String>> {
+        if !self.path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        let content = std::fs::read_to_string(&self.path)?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// Writes to a temporary file that's only ever readable by the current
+    /// user, and renames it into place
+    fn write(&self, secrets: &BTreeMap<String, String>) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        // Left over from a crash, and possibly with other permissions
+        if temp_path.exists() {
+            std::fs::remove_file(&temp_path)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&temp_path)?;
+        file.write_all(serde_json::to_string_pretty(secrets)?.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+impl SecretStore for FileStore {
+    fn get(&self, id: usize) -> Result<Option<String>> {
+        Ok(self.read()?.remove(&account(id)))
+    }
+
+    fn set(&self, id: usize, secret: &str) -> Result<()> {
+        if secret.is_empty() {
+            return self.delete(id);
+        }
+        let mut secrets = self.read()?;
+        secrets.insert(account(id), secret.to_string());
+        self.write(&secrets)
+    }
+
+    fn delete(&self, id: usize) -> Result<()> {
+        let mut secrets = self.read()?;
+        if secrets.remove(&account(id)).is_some() {
+            self.write(&secrets)?;
+        }
+        Ok(())
+    }
+}
+
+fn secrets_path() -> PathBuf {
+    dirs::data_dir()
+        .expect("No data dir")
+        .join("dbterm")
+        .join("secrets.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> FileStore {
+        let dir =
+            std::env::temp_dir().join(format!("dbterm-secrets-{}-{}", name, std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+        FileStore::new(dir.join("secrets.json"))
+    }
+
+    #[test]
+    fn file_store_round_trip() {
+        let store = temp_store("round-trip");
+        assert_eq!(store.get(1).unwrap(), None);
+        store.set(1, "hunter2").unwrap();
+        store.set(2, "swordfish").unwrap();
+        assert_eq!(store.get(1).unwrap().as_deref(), Some("hunter2"));
+        assert_eq!(store.get(2).unwrap().as_deref(), Some("swordfish"));
+        store.delete(1).unwrap();
+        assert_eq!(store.get(1).unwrap(), None);
+        assert_eq!(store.get(2).unwrap().as_deref(), Some("swordfish"));
+    }
+
+    #[test]
+    fn file_store_empty_secret_deletes() {
+        let store = temp_store("empty");
+        store.set(1, "hunter2").unwrap();
+        store.set(1, "").unwrap();
+        assert_eq!(store.get(1).unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_store_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let store = temp_store("private");
+        store.set(1, "hunter2").unwrap();
+        let mode = std::fs::metadata(&store.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(!store.path.with_extension("json.tmp").exists());
+    }
+}
@@ -0,0 +1,409 @@
+//! Just enough understanding of SQL text to tell what a statement will do
+//! before it's sent.
+
+use crate::data::DatabaseType;
+
+/// Leading keywords of statements that only read. Anything else is assumed
+/// to write, so unknown statements are refused rather than let through.
+const READ_KEYWORDS: &[&str] = &[
+    "select", "with", "values", "table", "show", "explain", "describe", "desc", "pragma",
+];
+
+/// Leading keywords of statements that return rows without only reading
+const ROW_KEYWORDS: &[&str] = &["call", "fetch"];
+
+/// Keywords that make an otherwise reading statement write, like a
+/// data-modifying CTE or `explain analyze delete ...`
+const WRITE_KEYWORDS: &[&str] = &[
+    "insert", "update", "delete", "merge", "truncate", "drop", "create", "alter", "into",
+];
+
+/// The words of `sql` in lowercase, skipping comments, string literals,
+/// quoted identifiers and dollar quotes the same way [`split`] does. Other
+/// punctuation is returned a character at a time.
+pub fn tokens(sql: &str, database_type: DatabaseType) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut i = 0;
+    while let Some(c) = sql[i..].chars().next() {
+        if let Some(end) = skipped(sql, i, database_type) {
+            i = end;
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' {
+            let word = sql[i..]
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .map_or(sql.len(), |end| i + end);
+            tokens.push(sql[i..word].to_lowercase());
+            i = word;
+            continue;
+        }
+        if !c.is_whitespace() {
+            tokens.push(c.to_string());
+        }
+        i += c.len_utf8();
+    }
+    tokens
+}
+
+/// The statements of a script, split on the semicolons between them. Quoted
+/// strings and identifiers, Postgres dollar quotes and comments are skipped,
+/// so semicolons in them don't split anything. Statements that are empty or
+/// only comments are left out.
+pub fn split(sql: &str, database_type: DatabaseType) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut start = 0;
+    let mut i = 0;
+    while i < sql.len() {
+        if let Some(end) = skipped(sql, i, database_type) {
+            i = end;
+        } else if sql.as_bytes()[i] == b';' {
+            statements.push(&sql[start..i]);
+            start = i + 1;
+            i += 1;
+        } else {
+            i += 1;
+        }
+    }
+    statements.push(&sql[start..]);
+    statements
+        .into_iter()
+        .map(str::trim)
+        .filter(|statement| !tokens(statement, database_type).is_empty())
+        .collect()
+}
+
+/// Where the comment, quoted string or identifier, or dollar quote starting
+/// at byte `i` of `sql` ends, if one starts there
+fn skipped(sql: &str, i: usize, database_type: DatabaseType) -> Option<usize> {
+    let bytes = sql.as_bytes();
+    let word_before = i > 0 && is_word_byte(bytes[i - 1]);
+    match bytes[i] {
+        b'-' if bytes.get(i + 1) == Some(&b'-') => {
+            Some(sql[i..].find('\n').map_or(bytes.len(), |end| i + end + 1))
+        }
+        b'/' if bytes.get(i + 1) == Some(&b'*') => Some(block_comment_end(bytes, i)),
+        // Postgres only takes backslash escapes in `E'...'` strings
+        b'E' | b'e'
+            if database_type == DatabaseType::Postgres
+                && bytes.get(i + 1) == Some(&b'\'')
+                && !word_before =>
+        {
+            Some(quoted_end(bytes, i + 1, true))
+        }
+        // MySQL takes them in every string, but not in identifiers
+        b'\'' | b'"' => Some(quoted_end(bytes, i, database_type == DatabaseType::Mysql)),
+        b'`' => Some(quoted_end(bytes, i, false)),
+        // `$` can be part of an identifier, which a dollar quote can't follow
+        b'$' if !word_before => {
+            let tag = dollar_tag(&sql[i..])?;
+            let body = i + tag.len();
+            Some(
+                sql[body..]
+                    .find(tag)
+                    .map_or(bytes.len(), |end| body + end + tag.len()),
+            )
+        }
+        _ => None,
+    }
+}
+
+/// Where the quoted string or identifier starting at `start` ends. A doubled
+/// quote is an escaped one, and simply reads as two strings back to back.
+/// With `escapes`, so is a quote after a backslash.
+fn quoted_end(bytes: &[u8], start: usize, escapes: bool) -> usize {
+    let quote = bytes[start];
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if escapes => i += 2,
+            byte if byte == quote => return i + 1,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Where the block comment starting at `start` ends. Postgres lets them nest.
+fn block_comment_end(bytes: &[u8], start: usize) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i + 1 < bytes.len() {
+        match (bytes[i], bytes[i + 1]) {
+            (b'/', b'*') => {
+                depth += 1;
+                i += 2;
+            }
+            (b'*', b'/') => {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return i;
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// The `$tag$` or `$$` that `sql` starts with, if it starts a dollar quote
+/// rather than a parameter like `$1`
+fn dollar_tag(sql: &str) -> Option<&str> {
+    let tag = &sql.as_bytes()[1..];
+    let end = tag.iter().position(|&b| !is_word_byte(b))?;
+    if tag[end] != b'$' || tag.first().is_some_and(u8::is_ascii_digit) {
+        return None;
+    }
+    Some(&sql[..end + 2])
+}
+
+fn is_word_byte(b: u8) -> bool {
+    // Non-ASCII bytes are parts of letters
+    b.is_ascii_alphanumeric() || b == b'_' || !b.is_ascii()
+}
+
+/// Whether running `statement` could change data, schema or session
+/// settings. Errs on the side of saying it does. Scripts should be [`split`]
+/// first, so each of their statements is looked at on its own.
+pub fn is_write(statement: &str, database_type: DatabaseType) -> bool {
+    let tokens = tokens(statement, database_type);
+    let Some(first) = tokens.first() else {
+        return false;
+    };
+    if !READ_KEYWORDS.contains(&first.as_str()) {
+        return true;
+    }
+    // `pragma query_only = off` would undo read-only mode
+    if first == "pragma" && tokens.iter().any(|token| token == "=") {
+        return true;
+    }
+    tokens
+        .iter()
+        .any(|token| WRITE_KEYWORDS.contains(&token.as_str()))
+}
+
+/// Whether `statement` starts a transaction, which it could leave open
+pub fn begins_transaction(statement: &str, database_type: DatabaseType) -> bool {
+    match tokens(statement, database_type).as_slice() {
+        [first, ..] if first == "begin" || first == "savepoint" => true,
+        [first, second, ..] => first == "start" && second == "transaction",
+        _ => false,
+    }
+}
+
+/// Whether `sql` returns rows, rather than a count of the rows it affected.
+/// Writes with a `returning` clause return rows too.
+pub fn returns_rows(sql: &str, database_type: DatabaseType) -> bool {
+    let tokens = tokens(sql, database_type);
+    let Some(first) = tokens.first() else {
+        return false;
+    };
+    READ_KEYWORDS.contains(&first.as_str())
+        || ROW_KEYWORDS.contains(&first.as_str())
+        || tokens.iter().any(|token| token == "returning")
+}
+
+/// Whether any statement of `sql` drops or truncates anything, or deletes or
+/// updates every row of a table for lack of a `where` clause
+pub fn is_destructive(sql: &str, database_type: DatabaseType) -> bool {
+    split(sql, database_type).into_iter().any(|statement| {
+        let tokens = tokens(statement, database_type);
+        let Some(first) = tokens.first() else {
+            return false;
+        };
+        let has = |keyword: &str| tokens.iter().any(|token| token == keyword);
+        match first.as_str() {
+            "drop" | "truncate" => true,
+            // `alter table ... drop column`
+            "alter" => has("drop"),
+            // Including data-modifying CTEs
+            "delete" | "update" | "with" => has_unfiltered_write(&tokens),
+            _ => false,
+        }
+    })
+}
+
+/// Whether a `delete` or `update` in `tokens` has no `where` of its own. Its
+/// own is one at the same depth of parentheses before the group it's in
+/// closes, so a `where` in a subquery doesn't count.
+fn has_unfiltered_write(tokens: &[String]) -> bool {
+    tokens.iter().enumerate().any(|(i, token)| {
+        // Only where they start a statement, not in `for update` or
+        // `on delete cascade`
+        let starts = i == 0 || matches!(tokens[i - 1].as_str(), "(" | ")");
+        if !(starts && matches!(token.as_str(), "delete" | "update")) {
+            return false;
+        }
+        let mut inner = 0;
+        for token in &tokens[i + 1..] {
+            match token.as_str() {
+                "(" => inner += 1,
+                ")" if inner == 0 => return true,
+                ")" => inner -= 1,
+                "where" if inner == 0 => return false,
+                _ => {}
+            }
+        }
+        true
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Postgres, unless a test says otherwise
+    fn tokens(sql: &str) -> Vec<String> {
+        super::tokens(sql, DatabaseType::Postgres)
+    }
+
+    fn split(sql: &str) -> Vec<&str> {
+        super::split(sql, DatabaseType::Postgres)
+    }
+
+    fn is_write(statement: &str) -> bool {
+        super::is_write(statement, DatabaseType::Postgres)
+    }
+
+    fn is_destructive(sql: &str) -> bool {
+        super::is_destructive(sql, DatabaseType::Postgres)
+    }
+
+    fn begins_transaction(statement: &str) -> bool {
+        super::begins_transaction(statement, DatabaseType::Postgres)
+    }
+
+    #[test]
+    fn tokens_skip_dollar_quotes() {
+        assert_eq!(
+            tokens("select $$'$$; drop table users; -- '"),
+            ["select", ";", "drop", "table", "users", ";"]
+        );
+        assert_eq!(
+            tokens("select $body$ drop $body$, $1"),
+            ["select", ",", "$", "1"]
+        );
+    }
+
+    #[test]
+    fn tokens_skip_nested_comments() {
+        assert_eq!(tokens("select /* a /* b */ drop */ 1"), ["select", "1"]);
+    }
+
+    #[test]
+    fn writes_hidden_after_quotes_are_found() {
+        let script = "select $$'$$; drop table users; -- '";
+        assert!(split(script).into_iter().any(is_write));
+        assert!(!is_write("select 'drop table users'"));
+        assert!(is_write(
+            "with gone as (delete from users returning *) select * from gone"
+        ));
+        assert!(is_write("pragma query_only = off"));
+    }
+
+    #[test]
+    fn destructive_needs_a_where_of_its_own() {
+        assert!(is_destructive("delete from users"));
+        assert!(!is_destructive("delete from users where id = 1"));
+        assert!(is_destructive(
+            "delete from users using (select id from bans where active) b"
+        ));
+        assert!(is_destructive(
+            "update users set banned = (select true from bans where bans.id = users.id)"
+        ));
+        assert!(!is_destructive(
+            "update users set name = 'x' where id in (select id from bans where active)"
+        ));
+        assert!(is_destructive(
+            "with gone as (delete from users returning *) select * from gone where true"
+        ));
+        assert!(!is_destructive(
+            "select * from users where id = 1 for update"
+        ));
+        assert!(is_destructive("select 1; drop table users"));
+        assert!(!is_destructive("select 'delete from users'"));
+    }
+
+    #[test]
+    fn split_skips_quotes() {
+        assert_eq!(
+            split("select 'a;b'; select \"c;d\"; select `e;f`"),
+            ["select 'a;b'", "select \"c;d\"", "select `e;f`"]
+        );
+        assert_eq!(split("select 'it''s; fine'"), ["select 'it''s; fine'"]);
+    }
+
+    #[test]
+    fn split_skips_dollar_quotes() {
+        let function = "create function f() returns int as $body$ select 1; $body$ language sql";
+        assert_eq!(
+            split(&format!("{}; select f()", function)),
+            [function, "select f()"]
+        );
+        assert_eq!(
+            split("select $$a;b$$; select $1"),
+            ["select $$a;b$$", "select $1"]
+        );
+        assert_eq!(split("select a$b; select 2"), ["select a$b", "select 2"]);
+    }
+
+    #[test]
+    fn split_skips_nested_comments() {
+        assert_eq!(
+            split("select 1 /* a /* b; */ c; */; select 2"),
+            ["select 1 /* a /* b; */ c; */", "select 2"]
+        );
+    }
+
+    #[test]
+    fn split_drops_trailing_comments() {
+        assert_eq!(split("select 1; -- done"), ["select 1"]);
+        assert_eq!(split("select 1;\n/* done; */\n"), ["select 1"]);
+        assert_eq!(split("select 1 -- one; two"), ["select 1 -- one; two"]);
+        assert!(split(" ; ;").is_empty());
+    }
+
+    #[test]
+    fn transactions_are_found() {
+        assert!(begins_transaction("BEGIN"));
+        assert!(begins_transaction("start transaction read only"));
+        assert!(begins_transaction("savepoint a"));
+        assert!(!begins_transaction("select 'begin'"));
+        assert!(!begins_transaction("start slave"));
+    }
+
+    #[test]
+    fn mysql_strings_take_backslash_escapes() {
+        let script = "select 'a\\' , '; drop table t; -- '";
+        let mysql = DatabaseType::Mysql;
+        assert_eq!(
+            super::split(script, mysql),
+            ["select 'a\\' , '", "drop table t"]
+        );
+        assert!(super::split(script, mysql)
+            .into_iter()
+            .any(|statement| super::is_write(statement, mysql)));
+        assert!(super::is_destructive(script, mysql));
+        assert_eq!(
+            super::split("select \"a\\\"; b\"; select `c\\`; select 1", mysql),
+            ["select \"a\\\"; b\"", "select `c\\`", "select 1"]
+        );
+        // Standard strings in Postgres end at the first quote
+        assert_eq!(split(script), [script]);
+    }
+
+    #[test]
+    fn postgres_escape_strings_take_backslash_escapes() {
+        let script = "select E'a\\' , '; drop table t; -- '";
+        assert_eq!(split(script), ["select E'a\\' , '", "drop table t"]);
+        assert!(is_destructive(script));
+        assert_eq!(
+            split("select e'\\\\'; select 1"),
+            ["select e'\\\\'", "select 1"]
+        );
+        assert_eq!(
+            split("select name'a\\'; select 1"),
+            ["select name'a\\'", "select 1"]
+        );
+    }
+}
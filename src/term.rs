@@ -5,7 +5,7 @@ use std::{
 
 use color_eyre::{eyre::WrapErr, Result};
 use crossterm::{
-    event::{self, Event},
+    event::{self, DisableBracketedPaste, EnableBracketedPaste, Event},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -16,6 +16,8 @@ pub fn init() -> Result<Terminal<CrosstermBackend<Stdout>>> {
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)
         .wrap_err("enter alternate screen")?;
+    // Delivers pasted text as a single event instead of a burst of key presses
+    execute!(stdout, EnableBracketedPaste).wrap_err("enable bracketed paste")?;
     let backend = CrosstermBackend::new(stdout);
     let terminal = Terminal::new(backend)?;
     Ok(terminal)
@@ -23,6 +25,7 @@ pub fn init() -> Result<Terminal<CrosstermBackend<Stdout>>> {
 
 pub fn restore<W: Write>(writer: &mut W) -> Result<()> {
     disable_raw_mode().context("disable raw mode")?;
+    execute!(writer, DisableBracketedPaste).wrap_err("disable bracketed paste")?;
     execute!(writer, LeaveAlternateScreen)
         .wrap_err("leave alternate screen")?;
     Ok(())
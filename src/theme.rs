@@ -1,6 +1,8 @@
-use ratatui::style::Style;
+use ratatui::style::{Color, Style};
 use serde::{Deserialize, Serialize};
 
+use crate::data::Environment;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub(crate) struct Theme {
     pub popup: Style,
@@ -12,3 +14,12 @@ pub(crate) fn default_theme() -> Theme {
         popup: Style::default(),
     }
 }
+
+/// The color connections in an environment are tinted with, so prod stands out
+pub(crate) fn environment_color(environment: Environment) -> Color {
+    match environment {
+        Environment::Dev => Color::Green,
+        Environment::Staging => Color::Yellow,
+        Environment::Prod => Color::Red,
+    }
+}
@@ -0,0 +1,197 @@
+use std::{
+    net::{Ipv4Addr, TcpListener},
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::{eyre, Result};
+use tokio::{
+    io::AsyncReadExt,
+    net::TcpStream,
+    process::{Child, Command},
+};
+
+use crate::data::SshConfig;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A local port forward through an ssh jump host, backed by the system `ssh`
+/// binary so agents, `~/.ssh/config` and known hosts all work as usual. The
+/// tunnel closes when this is dropped.
+#[derive(Debug)]
+pub struct Tunnel {
+    _child: Child,
+    local_port: u16,
+}
+
+impl Tunnel {
+    /// Forwards a free local port to `remote_host:remote_port`, as seen from the
+    /// jump host, and waits until the forward accepts connections.
+    pub async fn open(ssh: &SshConfig, remote_host: &str, remote_port: u16) -> Result<Self> {
+        let local_port = free_port()?;
+        let mut command = Command::new("ssh");
+        command
+            .args(ssh_args(ssh, local_port, remote_host, remote_port))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+
+        let mut child = command
+            .spawn()
+            .map_err(|e| eyre!("SSH tunnel: could not start ssh: {}", e))?;
+
+        let deadline = Instant::now() + CONNECT_TIMEOUT;
+        loop {
+            if let Some(status) = child.try_wait()? {
+                let mut stderr = String::new();
+                if let Some(mut pipe) = child.stderr.take() {
+                    pipe.read_to_string(&mut stderr).await.ok();
+                }
+                let reason = failure_reason(&stderr, &status);
+                return Err(eyre!("SSH tunnel to {} failed: {}", ssh.host, reason));
+            }
+            // ssh only listens on the forwarded port once it has authenticated
+            if TcpStream::connect((Ipv4Addr::LOCALHOST, local_port))
+                .await
+                .is_ok()
+            {
+                // ssh logs every forward that fails to open from here on, and
+                // would stop once the pipe is full if nothing read it
+                if let Some(mut pipe) = child.stderr.take() {
+                    tokio::spawn(async move {
+                        tokio::io::copy(&mut pipe, &mut tokio::io::sink())
+                            .await
+                            .ok();
+                    });
+                }
+                return Ok(Self {
+                    _child: child,
+                    local_port,
+                });
+            }
+            if Instant::now() >= deadline {
+                return Err(eyre!("SSH tunnel to {} timed out", ssh.host));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    pub fn local_port(&self) -> u16 {
+        self.local_port
+    }
+}
+
+/// The arguments to `ssh` that forward `local_port` to
+/// `remote_host:remote_port`
+fn ssh_args(ssh: &SshConfig, local_port: u16, remote_host: &str, remote_port: u16) -> Vec<String> {
+    let destination = if ssh.user.is_empty() {
+        ssh.host.clone()
+    } else {
+        format!("{}@{}", ssh.user, ssh.host)
+    };
+    let mut args = vec![
+        "-N".to_string(),
+        "-L".to_string(),
+        format!("127.0.0.1:{}:{}:{}", local_port, remote_host, remote_port),
+        "-p".to_string(),
+        ssh.port.to_string(),
+    ];
+    // There's no terminal to prompt for passwords or passphrases on
+    args.extend(["-o", "BatchMode=yes", "-o", "ExitOnForwardFailure=yes"].map(String::from));
+    if let Some(identity_file) = &ssh.identity_file {
+        args.extend(["-i".to_string(), identity_file.clone()]);
+        args.extend(["-o", "IdentitiesOnly=yes"].map(String::from));
+    }
+    // So a host starting with `-` isn't taken for an option
+    args.extend(["--".to_string(), destination]);
+    args
+}
+
+/// Why ssh exited, which it says in the last line it logged
+fn failure_reason(stderr: &str, status: &impl std::fmt::Display) -> String {
+    match stderr.trim() {
+        "" => status.to_string(),
+        stderr => stderr.lines().last().unwrap_or(stderr).trim().to_string(),
+    }
+}
+
+fn free_port() -> Result<u16> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .map_err(|e| eyre!("SSH tunnel: no free local port: {}", e))?;
+    Ok(listener.local_addr()?.port())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ssh(user: &str, identity_file: Option<&str>) -> SshConfig {
+        SshConfig {
+            host: "bastion.example.com".to_string(),
+            port: 2222,
+            user: user.to_string(),
+            identity_file: identity_file.map(String::from),
+        }
+    }
+
+    #[test]
+    fn args_forward_the_port() {
+        let args = ssh_args(&ssh("deploy", None), 40000, "db.internal", 5432);
+        assert_eq!(
+            args,
+            [
+                "-N",
+                "-L",
+                "127.0.0.1:40000:db.internal:5432",
+                "-p",
+                "2222",
+                "-o",
+                "BatchMode=yes",
+                "-o",
+                "ExitOnForwardFailure=yes",
+                "--",
+                "deploy@bastion.example.com",
+            ]
+        );
+    }
+
+    #[test]
+    fn args_use_the_identity_file() {
+        let args = ssh_args(&ssh("", Some("~/.ssh/id_db")), 40000, "db", 5432);
+        let end = &args[args.len() - 6..];
+        assert_eq!(
+            end,
+            [
+                "-i",
+                "~/.ssh/id_db",
+                "-o",
+                "IdentitiesOnly=yes",
+                "--",
+                "bastion.example.com"
+            ]
+        );
+    }
+
+    #[test]
+    fn args_end_options_before_the_host() {
+        let mut config = ssh("", None);
+        config.host = "-oProxyCommand=evil".to_string();
+        let args = ssh_args(&config, 40000, "db", 5432);
+        assert_eq!(&args[args.len() - 2..], ["--", "-oProxyCommand=evil"]);
+    }
+
+    #[test]
+    fn reason_is_the_last_line_logged() {
+        let stderr = "Warning: Permanently added 'bastion' to the list of known hosts.\r\n\
+                      deploy@bastion: Permission denied (publickey).\n";
+        assert_eq!(
+            failure_reason(stderr, &"exit status: 255"),
+            "deploy@bastion: Permission denied (publickey)."
+        );
+        assert_eq!(
+            failure_reason("  \n", &"exit status: 255"),
+            "exit status: 255"
+        );
+    }
+}